@@ -1,27 +1,121 @@
-use command::Command;
+use arguments::Arguments;
+use bufferlist::BufferList;
+use command::{Command, Direction};
+use commandbar::CommandBar;
 use crossterm::event::{
-    read,
     Event::{self},
     KeyEvent, KeyEventKind,
 };
+use encoding_rs::Encoding;
+use layout::{Layout, Separator, SplitDirection};
+use messagebar::MessageBar;
+use statusbar::StatusBar;
 use std::panic::{set_hook, take_hook};
-use std::{env, io::Error};
-use terminal::Terminal;
-use view::View;
+use std::{
+    env,
+    io::{self, Error, ErrorKind, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+use terminal::{Rect, Size, Terminal, WidthModel};
+use view::{position::Position, set_large_file_threshold, Indentation, View, WhitespaceGlyphs};
 
+mod arguments;
+mod bufferlist;
 mod command;
+mod commandbar;
+mod documentstatus;
+mod layout;
+mod messagebar;
+mod statusbar;
 mod terminal;
+#[cfg(test)]
+mod tests;
 mod view;
 
+// A destructive command waiting to be repeated, after warning about unsaved changes
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum Confirmation {
+    #[default]
+    None,
+    Close,
+    Quit,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum PromptType {
+    #[default]
+    None,
+    Open,
+    Encoding,
+    FindBytes,
+    Recover,
+    // Editing a large file takes reading all of it into memory
+    ReadWholeFile,
+    SaveAs,
+    // Saving would replace a file the buffer isn't about, see `Editor::overwrite_path`
+    Overwrite,
+    // The file was changed by another program, possibly noticed while saving
+    Reload {
+        then_save: bool,
+    },
+}
+
+// Unsaved changes are written to swap files whenever no key was pressed for this long,
+// and at least this often while typing
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+const JOURNAL_INTERVAL: Duration = Duration::from_secs(2);
+// Followed files are looked at when idle as well, and at least this often while keys are pressed
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+// Views on screen are the leaves of `layout`, the others belong to hidden buffers
+#[allow(clippy::struct_excessive_bools)]
 pub struct Editor {
     should_exit: bool,
-    view: View,
+    views: Vec<View>,
+    layout: Layout,
+    active_view: usize,
+    separators: Vec<Separator>,
+    layout_needs_redraw: bool,
+    status_bar: StatusBar,
+    message_bar: MessageBar,
+    command_bar: CommandBar,
+    prompt_type: PromptType,
+    // Where Save-As writes once replacing the file there is confirmed
+    overwrite_path: Option<String>,
+    buffer_list: Option<BufferList>,
+    pending_confirmation: Confirmation,
+    terminal_size: Size,
+    show_whitespace: bool,
+    whitespace_glyphs: WhitespaceGlyphs,
+    // Keep the previous contents of a saved file as `file~`
+    keep_backups: bool,
+    // Keep unsaved changes in swap files, to recover them after a crash
+    journal_changes: bool,
+    last_journal: Instant,
+    last_follow: Instant,
+    // Write the first buffer to stdout on quitting, see `Editor::output`
+    output_to_stdout: bool,
 }
 
 impl Drop for Editor {
     fn drop(&mut self) {
+        // A panic unwinding through the editor is the last chance to keep unsaved changes
+        if thread::panicking() && self.journal_changes {
+            for view in &mut self.views {
+                let _ = view.journal();
+            }
+        }
         let _ = Terminal::terminate();
         let _ = Terminal::print("Goodbye! :D\n");
+        // Only once the terminal is left, in case stdout is the terminal after all
+        if self.output_to_stdout && self.should_exit && !thread::panicking() {
+            if let Ok(bytes) = self.output() {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&bytes).and_then(|()| stdout.flush());
+            }
+        }
     }
 }
 
@@ -34,19 +128,89 @@ impl Editor {
             current_panic_hook(info);
         }));
 
+        let arguments = Arguments::parse(env::args().skip(1))
+            .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+
+        Terminal::initialize()?;
+        Ok(Self::start(&arguments))
+    }
+
+    // Run the editor on another terminal backend, e.g. an in-memory one in tests
+    #[cfg(test)]
+    pub fn with_backend(
+        backend: Box<dyn terminal::Backend>,
+        arguments: &Arguments,
+    ) -> Result<Self, Error> {
+        Terminal::set_backend(backend);
         Terminal::initialize()?;
+        Ok(Self::start(arguments))
+    }
 
+    fn start(arguments: &Arguments) -> Self {
         // Initialize editor attributes
-        let mut view: View = View::default();
-        let args: Vec<String> = env::args().collect::<Vec<String>>(); // retrieve file path to load from arguments
-        if let Some(file_path) = args.get(1) {
-            view.load(file_path);
+        let mut editor = Self {
+            should_exit: false,
+            views: vec![View::default()],
+            layout: Layout::default(),
+            active_view: 0,
+            separators: Vec::new(),
+            layout_needs_redraw: true,
+            status_bar: StatusBar::default(),
+            message_bar: MessageBar::default(),
+            command_bar: CommandBar::default(),
+            prompt_type: PromptType::None,
+            overwrite_path: None,
+            buffer_list: None,
+            pending_confirmation: Confirmation::None,
+            terminal_size: Size::default(),
+            show_whitespace: arguments.show_whitespace,
+            whitespace_glyphs: arguments.whitespace_glyphs,
+            keep_backups: arguments.backup,
+            journal_changes: !arguments.no_swap,
+            last_journal: Instant::now(),
+            last_follow: Instant::now(),
+            output_to_stdout: arguments.stdout,
+        };
+        editor.resize(Terminal::size().unwrap_or_default());
+        editor
+            .message_bar
+            .update_message("HELP: Ctrl-S save | Ctrl-O open | Ctrl-B buffers | Ctrl-Q quit");
+
+        if arguments.probe_widths {
+            if let Err(err) = Terminal::calibrate_widths() {
+                editor
+                    .message_bar
+                    .update_message(&format!("Could not measure character widths: {err}"));
+            }
+        }
+        if let Some(size) = arguments.large_file {
+            set_large_file_threshold(size.saturating_mul(1024 * 1024));
+        }
+        if let Some(indent) = arguments.indent {
+            Indentation::set_current(indent);
+        }
+        if let Some(ambiguous_width) = arguments.ambiguous_width {
+            WidthModel::set_current(WidthModel {
+                ambiguous_width,
+                ..WidthModel::current()
+            });
         }
 
-        Ok(Self {
-            should_exit: false,
-            view,
-        })
+        if let Some(file_path) = &arguments.file_path {
+            if file_path == "-" {
+                editor.open_stdin();
+            } else {
+                editor.open(file_path);
+            }
+            if arguments.readonly {
+                editor.active_view().set_read_only(true);
+            }
+            if arguments.follow {
+                editor.toggle_follow();
+            }
+        }
+
+        editor
     }
 
     pub fn run(&mut self) {
@@ -56,10 +220,26 @@ impl Editor {
                 break;
             }
 
-            match read() {
+            if !Terminal::poll_event(IDLE_TIMEOUT).unwrap_or(true) {
+                self.journal();
+                self.read_followed_files();
+                self.take_whole_files();
+                self.check_disk_changes(false);
+                continue;
+            }
+            if self.last_journal.elapsed() >= JOURNAL_INTERVAL {
+                self.journal();
+            }
+            if self.last_follow.elapsed() >= FOLLOW_INTERVAL {
+                self.read_followed_files();
+            }
+
+            match Terminal::read_event() {
                 Ok(event) => {
                     self.handle_event(event);
                 }
+                // The event source ran out (only happens with a scripted backend)
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
                 Err(err) => {
                     #[cfg(debug_assertions)]
                     {
@@ -69,22 +249,123 @@ impl Editor {
             }
         }
     }
-    pub fn handle_args(&mut self) {
-        let args = env::args().collect::<Vec<String>>();
 
-        if let Some(file_path) = args.get(1) {
-            self.view.load(file_path);
+    // Keep the unsaved changes of every buffer in its swap file
+    fn journal(&mut self) {
+        self.last_journal = Instant::now();
+        if !self.journal_changes {
+            return;
+        }
+        for view_index in self.buffer_views() {
+            if let Err(err) = self.views[view_index].journal() {
+                self.message_bar
+                    .update_message(&format!("Could not write the swap file: {err}"));
+            }
+        }
+    }
+
+    // Load what was appended to the files followed in any buffer
+    fn read_followed_files(&mut self) {
+        self.last_follow = Instant::now();
+        for view_index in self.buffer_views() {
+            if let Err(err) = self.views[view_index].read_appended() {
+                self.message_bar
+                    .update_message(&format!("Could not read the followed file: {err}"));
+            }
+        }
+    }
+
+    // Take in the large files read in whole in the background meanwhile
+    fn take_whole_files(&mut self) {
+        for view_index in self.buffer_views() {
+            match self.views[view_index].take_whole_file() {
+                Ok(true) => self
+                    .message_bar
+                    .update_message("The whole file was read, it can be edited now."),
+                Ok(false) => {}
+                Err(err) => self
+                    .message_bar
+                    .update_message(&format!("Could not read the file: {err}")),
+            }
+        }
+    }
+
+    fn active_view(&mut self) -> &mut View {
+        &mut self.views[self.active_view]
+    }
+
+    fn view_area(&self) -> Rect {
+        // The two bottom rows are reserved for the status bar and the message/command bar
+        Rect {
+            origin: Position::default(),
+            size: Size {
+                width: self.terminal_size.width,
+                height: self.terminal_size.height.saturating_sub(2),
+            },
         }
     }
 
     fn refresh_screen(&mut self) {
-        let _ = Terminal::hide_caret();
-        self.view.render();
-        let _ = Terminal::move_caret_to(&self.view.get_position());
-        let _ = Terminal::show_caret();
+        if self.terminal_size.height == 0 || self.terminal_size.width == 0 {
+            return;
+        }
+        Terminal::hide_caret();
+
+        let view_area = self.view_area();
+        if let Some(buffer_list) = &mut self.buffer_list {
+            buffer_list.render(&view_area);
+        } else {
+            if self.layout_needs_redraw {
+                self.render_separators();
+            }
+            let whitespace = self.show_whitespace.then_some(self.whitespace_glyphs);
+            for view_index in self.layout.pane_views() {
+                self.views[view_index].render(whitespace);
+            }
+        }
+
+        if self.terminal_size.height > 1 {
+            let mut status = self.views[self.active_view].get_status();
+            status.buffer_index = self.buffer_position(self.active_view);
+            status.buffer_count = self.buffer_views().len();
+            self.status_bar.update_status(status);
+            self.status_bar.render();
+        }
+
+        let caret_position = if self.prompt_type == PromptType::None {
+            self.message_bar.render();
+            self.views[self.active_view].get_position()
+        } else {
+            self.command_bar.render();
+            Position {
+                row: self.terminal_size.height.saturating_sub(1),
+                col: self.command_bar.caret_col(),
+            }
+        };
+
+        Terminal::move_caret_to(&caret_position);
+        if self.buffer_list.is_none() {
+            Terminal::show_caret();
+        }
         let _ = Terminal::execute();
     }
 
+    fn render_separators(&mut self) {
+        for separator in &self.separators {
+            match separator.direction {
+                SplitDirection::Horizontal => {
+                    Terminal::print_row(&separator.area, 0, &"─".repeat(separator.area.size.width));
+                }
+                SplitDirection::Vertical => {
+                    for row in 0..separator.area.size.height {
+                        Terminal::print_row(&separator.area, row, "│");
+                    }
+                }
+            }
+        }
+        self.layout_needs_redraw = false;
+    }
+
     #[allow(clippy::needless_pass_by_value)]
     fn handle_event(&mut self, event: Event) {
         let should_execute: bool = match &event {
@@ -93,18 +374,751 @@ impl Editor {
             _ => false,
         };
 
+        if event == Event::FocusGained {
+            self.check_disk_changes(false);
+        }
         if !should_execute {
             return;
         }
 
-        match Command::try_from(event) {
-            Ok(command) => match command {
-                Command::Quit => self.should_exit = true,
-                _ => self.view.handle_command(command),
-            },
-            Err(_) => {
-                // Ignore error for both debug and release
+        // Ignore unsupported events for both debug and release
+        if let Ok(command) = Command::try_from(event) {
+            self.process_command(command);
+        }
+    }
+
+    fn process_command(&mut self, command: Command) {
+        if let Command::Resize(size) = command {
+            self.resize(size);
+            return;
+        }
+
+        if self.buffer_list.is_some() {
+            self.process_command_during_buffer_list(&command);
+        } else if self.prompt_type == PromptType::None {
+            self.process_command_no_prompt(command);
+        } else {
+            self.process_command_during_prompt(&command);
+        }
+    }
+
+    fn process_command_no_prompt(&mut self, command: Command) {
+        // Confirmations only hold for the very next command
+        let pending_confirmation = std::mem::take(&mut self.pending_confirmation);
+
+        match command {
+            Command::Quit => self.handle_quit(pending_confirmation == Confirmation::Quit),
+            Command::Save => self.save(),
+            Command::SaveAs => self.set_prompt(PromptType::SaveAs),
+            Command::Open => self.set_prompt(PromptType::Open),
+            Command::NextBuffer | Command::PreviousBuffer => {
+                let buffer_views = self.buffer_views();
+                let position = self.buffer_position(self.active_view);
+                let target = if matches!(command, Command::NextBuffer) {
+                    position
+                        .saturating_add(1)
+                        .checked_rem(buffer_views.len())
+                        .unwrap_or(0)
+                } else {
+                    position
+                        .checked_sub(1)
+                        .unwrap_or(buffer_views.len().saturating_sub(1))
+                };
+                self.show_buffer(buffer_views[target]);
+            }
+            Command::ListBuffers => {
+                let entries = self
+                    .buffer_views()
+                    .into_iter()
+                    .map(|view_index| self.views[view_index].get_status())
+                    .collect();
+                let selected = self.buffer_position(self.active_view);
+                self.buffer_list = Some(BufferList::new(entries, selected));
+            }
+            Command::CloseBuffer => self.handle_close(pending_confirmation == Confirmation::Close),
+            Command::Split(direction) => self.split(direction),
+            Command::FocusNextPane => {
+                let panes = self.layout.pane_views();
+                let position = panes
+                    .iter()
+                    .position(|view_index| *view_index == self.active_view)
+                    .unwrap_or(0);
+                self.active_view = panes[position
+                    .saturating_add(1)
+                    .checked_rem(panes.len())
+                    .unwrap_or(0)];
+            }
+            Command::ClosePane => self.close_pane(),
+            Command::ToggleWhitespace => self.show_whitespace = !self.show_whitespace,
+            Command::Inspect => {
+                let description = self.active_view().inspect_caret();
+                self.message_bar.update_message(&description);
+            }
+            Command::FindBytes => {
+                if self.views[self.active_view].is_hex() {
+                    self.set_prompt(PromptType::FindBytes);
+                } else {
+                    self.message_bar
+                        .update_message("Byte search is only available in the hex view.");
+                }
+            }
+            Command::ConvertLineEndings | Command::ToggleFinalNewline => {
+                self.change_line_format(&command);
+            }
+            Command::ToggleReadOnly => self.toggle_read_only(),
+            Command::ToggleFollow => self.toggle_follow(),
+            Command::ReopenWithEncoding => {
+                if self.views[self.active_view].file_path().is_none() {
+                    self.message_bar
+                        .update_message("Only a buffer read from a file can be reopened.");
+                } else if self.views[self.active_view].is_modified() {
+                    self.message_bar
+                        .update_message("Buffer has unsaved changes, save it before reopening.");
+                } else {
+                    self.set_prompt(PromptType::Encoding);
+                }
+            }
+            Command::ResizePane(direction) => {
+                let (split_direction, grow) = match direction {
+                    Direction::Up => (SplitDirection::Horizontal, false),
+                    Direction::Down => (SplitDirection::Horizontal, true),
+                    Direction::Left => (SplitDirection::Vertical, false),
+                    _ => (SplitDirection::Vertical, true),
+                };
+                if self.layout.resize(self.active_view, split_direction, grow) {
+                    self.arrange_panes();
+                }
+            }
+            _ => {
+                if let Err(err) = self.active_view().handle_command(command) {
+                    self.refuse_edit(&err);
+                }
+            }
+        }
+    }
+
+    // Tell why an edit was refused, or ask whether to read a large file in whole to make it
+    fn refuse_edit(&mut self, err: &Error) {
+        if err.kind() == ErrorKind::FileTooLarge {
+            self.set_prompt(PromptType::ReadWholeFile);
+        } else {
+            self.message_bar.update_message(&err.to_string());
+        }
+    }
+
+    fn toggle_read_only(&mut self) {
+        if self.views[self.active_view].is_following() {
+            self.message_bar
+                .update_message("Stop following the file (Alt-F) to edit it.");
+            return;
+        }
+        let read_only = !self.views[self.active_view].is_read_only();
+        self.active_view().set_read_only(read_only);
+        self.message_bar.update_message(if read_only {
+            "Buffer is read-only now."
+        } else {
+            "Buffer is editable now."
+        });
+    }
+
+    fn toggle_follow(&mut self) {
+        let message = match self.active_view().toggle_following() {
+            Ok(true) => "Following the file, new lines show up at the end.".to_string(),
+            Ok(false) => "Stopped following the file.".to_string(),
+            Err(err) => err.to_string(),
+        };
+        self.message_bar.update_message(&message);
+    }
+
+    fn change_line_format(&mut self, command: &Command) {
+        if self.views[self.active_view].is_hex() {
+            self.message_bar
+                .update_message("Binary files have no lines to change.");
+            return;
+        }
+        let result = match command {
+            Command::ConvertLineEndings => self
+                .active_view()
+                .convert_line_endings()
+                .map(|line_ending| format!("Line endings converted to {line_ending}.")),
+            _ => self.active_view().toggle_final_newline().map(|added| {
+                if added {
+                    "Added a final newline.".to_string()
+                } else {
+                    "Removed the final newline.".to_string()
+                }
+            }),
+        };
+        match result {
+            Ok(message) => self.message_bar.update_message(&message),
+            Err(err) => self.refuse_edit(&err),
+        }
+    }
+
+    fn process_command_during_prompt(&mut self, command: &Command) {
+        match command {
+            // Not answering keeps the buffer, rather than asking again and again
+            Command::Dismiss if matches!(self.prompt_type, PromptType::Reload { .. }) => {
+                self.set_prompt(PromptType::None);
+                self.answer_disk_change("k", false);
+            }
+            Command::Dismiss => self.set_prompt(PromptType::None),
+            Command::Insert('\t')
+                if matches!(self.prompt_type, PromptType::Open | PromptType::SaveAs) =>
+            {
+                self.command_bar.complete_path();
+            }
+            Command::Enter if self.prompt_type == PromptType::Overwrite => {
+                let value = self.command_bar.value().trim().to_lowercase();
+                self.set_prompt(PromptType::None);
+                match self.overwrite_path.take() {
+                    Some(file_path) if value == "y" || value == "yes" => {
+                        self.save_as(&file_path, true);
+                    }
+                    _ => self.message_bar.update_message("Not saved."),
+                }
+            }
+            Command::Enter if matches!(self.prompt_type, PromptType::Reload { .. }) => {
+                let value = self.command_bar.value().trim().to_lowercase();
+                let then_save = self.prompt_type == PromptType::Reload { then_save: true };
+                self.set_prompt(PromptType::None);
+                self.answer_disk_change(&value, then_save);
+            }
+            Command::Enter if self.prompt_type == PromptType::ReadWholeFile => {
+                let value = self.command_bar.value().trim().to_lowercase();
+                self.set_prompt(PromptType::None);
+                if value == "y" || value == "yes" {
+                    self.active_view().read_whole_file();
+                    self.message_bar
+                        .update_message("Reading the file, it can be edited once that is done.");
+                } else {
+                    self.message_bar.update_message(
+                        "Not read, a higher --large-file reads such files in whole.",
+                    );
+                }
+            }
+            Command::Enter if self.prompt_type == PromptType::Recover => {
+                let value = self.command_bar.value().trim().to_lowercase();
+                self.set_prompt(PromptType::None);
+                self.answer_recovery(&value);
+            }
+            Command::Enter => {
+                let value = self.command_bar.value().trim().to_string();
+                let prompt_type = self.prompt_type;
+                self.set_prompt(PromptType::None);
+                if value.is_empty() {
+                    return;
+                }
+                match prompt_type {
+                    PromptType::Open => self.open(&value),
+                    PromptType::SaveAs => self.save_as(&value, false),
+                    PromptType::Encoding => self.reopen_with_encoding(&value),
+                    PromptType::FindBytes => {
+                        let message = match self.active_view().find_bytes(&value) {
+                            Ok(offset) => format!("Found at offset {offset} (0x{offset:X})."),
+                            Err(err) => err,
+                        };
+                        self.message_bar.update_message(&message);
+                    }
+                    PromptType::Recover
+                    | PromptType::ReadWholeFile
+                    | PromptType::Overwrite
+                    | PromptType::Reload { .. }
+                    | PromptType::None => {}
+                }
+            }
+            _ => self.command_bar.handle_command(command),
+        }
+    }
+
+    fn process_command_during_buffer_list(&mut self, command: &Command) {
+        let Some(buffer_list) = &mut self.buffer_list else {
+            return;
+        };
+
+        match command {
+            Command::Dismiss => self.close_buffer_list(),
+            Command::Enter => {
+                let selected = buffer_list.selected();
+                self.close_buffer_list();
+                if let Some(view_index) = self.buffer_views().get(selected) {
+                    self.show_buffer(*view_index);
+                }
+            }
+            _ => buffer_list.handle_command(command),
+        }
+    }
+
+    fn resize(&mut self, size: Size) {
+        self.terminal_size = size;
+        Terminal::resize(size);
+        self.arrange_panes();
+        self.status_bar
+            .resize(Rect::row(size.height.saturating_sub(2), size.width));
+        self.message_bar
+            .resize(Rect::row(size.height.saturating_sub(1), size.width));
+        self.command_bar
+            .resize(Rect::row(size.height.saturating_sub(1), size.width));
+        if let Some(buffer_list) = &self.buffer_list {
+            let selected = buffer_list.selected();
+            let entries = self
+                .buffer_views()
+                .into_iter()
+                .map(|view_index| self.views[view_index].get_status())
+                .collect();
+            self.buffer_list = Some(BufferList::new(entries, selected));
+        }
+    }
+
+    fn set_prompt(&mut self, prompt_type: PromptType) {
+        match prompt_type {
+            PromptType::None => self.message_bar.set_needs_redraw(),
+            PromptType::Open => self.command_bar.set_prompt("Open: "),
+            PromptType::SaveAs => self.command_bar.set_prompt("Save as: "),
+            PromptType::Overwrite => self
+                .command_bar
+                .set_prompt("File exists. Overwrite it (y/n)? "),
+            PromptType::Encoding => self.command_bar.set_prompt("Reopen with encoding: "),
+            PromptType::FindBytes => self.command_bar.set_prompt("Find bytes: "),
+            PromptType::Recover => self
+                .command_bar
+                .set_prompt("Swap file found. Recover (r), diff (d) or discard (x)? "),
+            PromptType::ReadWholeFile => self
+                .command_bar
+                .set_prompt("Read the whole file into memory to edit it (y/n)? "),
+            PromptType::Reload { .. } => self
+                .command_bar
+                .set_prompt("File changed on disk. Reload (r), keep yours (k) or diff (d)? "),
+        }
+        // Saving under another name starts from the current one
+        let value = match prompt_type {
+            PromptType::SaveAs => self.views[self.active_view].file_path(),
+            _ => None,
+        };
+        self.command_bar.set_value(&value.unwrap_or_default());
+        self.prompt_type = prompt_type;
+    }
+
+    fn close_buffer_list(&mut self) {
+        self.buffer_list = None;
+        self.arrange_panes();
+    }
+
+    /**
+     * Pane Operations
+     */
+    // Recompute the area of every pane, which also redraws all of them
+    fn arrange_panes(&mut self) {
+        let mut panes = Vec::new();
+        self.separators.clear();
+        self.layout
+            .arrange(self.view_area(), &mut panes, &mut self.separators);
+        for (view_index, area) in panes {
+            self.views[view_index].resize(area);
+        }
+        self.layout_needs_redraw = true;
+    }
+
+    fn split(&mut self, direction: SplitDirection) {
+        let view = self.views[self.active_view].split();
+        self.views.push(view);
+        let new_view = self.views.len().saturating_sub(1);
+        self.layout.split(self.active_view, new_view, direction);
+        self.active_view = new_view;
+        self.arrange_panes();
+    }
+
+    fn close_pane(&mut self) {
+        if self.layout.pane_views().len() <= 1 {
+            self.message_bar
+                .update_message("Cannot close the last pane.");
+            return;
+        }
+
+        let closed_view = self.active_view;
+        self.layout.remove(closed_view);
+        self.active_view = self.layout.pane_views()[0];
+        self.release_view(closed_view);
+        self.arrange_panes();
+    }
+
+    // Replace the view of the focused pane
+    fn show_in_active_pane(&mut self, view_index: usize) {
+        if view_index == self.active_view {
+            return;
+        }
+        let previous_view = self.active_view;
+        self.layout.replace(previous_view, view_index);
+        self.active_view = view_index;
+        self.release_view(previous_view);
+        self.arrange_panes();
+    }
+
+    // Drop a view that left the screen, unless it is the only one remembering its buffer
+    fn release_view(&mut self, view_index: usize) {
+        let is_duplicate = self.views.iter().enumerate().any(|(index, view)| {
+            index != view_index && view.shares_buffer_with(&self.views[view_index])
+        });
+        if is_duplicate && !self.layout.contains(view_index) {
+            self.remove_view(view_index);
+        }
+    }
+
+    fn remove_view(&mut self, view_index: usize) {
+        self.views.remove(view_index);
+        self.layout.update_views(&|index| {
+            if index > view_index {
+                index.saturating_sub(1)
+            } else {
+                index
             }
+        });
+        if self.active_view > view_index {
+            self.active_view = self.active_view.saturating_sub(1);
+        }
+    }
+
+    /**
+     * Buffer Operations
+     */
+    // The first view of every distinct buffer, in the order they were opened
+    fn buffer_views(&self) -> Vec<usize> {
+        (0..self.views.len())
+            .filter(|index| {
+                !self.views[..*index]
+                    .iter()
+                    .any(|view| view.shares_buffer_with(&self.views[*index]))
+            })
+            .collect()
+    }
+
+    fn buffer_position(&self, view_index: usize) -> usize {
+        self.buffer_views()
+            .iter()
+            .position(|index| self.views[*index].shares_buffer_with(&self.views[view_index]))
+            .unwrap_or(0)
+    }
+
+    // Show the buffer of `view_index` in the focused pane, reusing a hidden view of it if there is one
+    fn show_buffer(&mut self, view_index: usize) {
+        if self.views[self.active_view].shares_buffer_with(&self.views[view_index]) {
+            return;
+        }
+
+        let hidden_view = (0..self.views.len()).find(|index| {
+            self.views[*index].shares_buffer_with(&self.views[view_index])
+                && !self.layout.contains(*index)
+        });
+        let target = hidden_view.unwrap_or_else(|| {
+            let view = self.views[view_index].split();
+            self.views.push(view);
+            self.views.len().saturating_sub(1)
+        });
+        self.show_in_active_pane(target);
+    }
+
+    fn open(&mut self, file_path: &str) {
+        // Focus the buffer instead of opening a second copy of the same file
+        if let Some(index) = self
+            .views
+            .iter()
+            .position(|view| view.file_path().as_deref() == Some(file_path))
+        {
+            self.show_buffer(index);
+            return;
+        }
+
+        let mut view = View::default();
+        if let Err(err) = view.load(file_path) {
+            self.message_bar
+                .update_message(&format!("Could not open {file_path}: {err}"));
+            return;
+        }
+        if let Some(warning) = view.decode_warning() {
+            self.message_bar.update_message(&warning);
+        }
+        if view.is_read_only() {
+            self.message_bar.update_message(&format!(
+                "{file_path} is not writable, opened read-only. Alt-R makes it editable."
+            ));
+        }
+        if view.is_new_file() {
+            self.message_bar
+                .update_message(&format!("New file {file_path}, saving creates it."));
+        }
+        let has_swap = view.swap().is_some();
+
+        self.views.push(view);
+        let new_view = self.views.len().saturating_sub(1);
+        let previous_view = self.active_view;
+        self.show_in_active_pane(new_view);
+
+        // Don't keep the welcome screen around as an extra buffer
+        if self.views[previous_view].is_blank() && !self.layout.contains(previous_view) {
+            self.remove_view(previous_view);
+        }
+
+        // Changes from an earlier session that never made it into the file
+        if has_swap {
+            self.set_prompt(PromptType::Recover);
+        }
+    }
+
+    // Show what was piped into the editor instead of the welcome screen
+    fn open_stdin(&mut self) {
+        let mut view = View::default();
+        if let Err(err) = view.load_stdin() {
+            self.message_bar
+                .update_message(&format!("Could not read stdin: {err}"));
+            return;
+        }
+        if let Some(warning) = view.decode_warning() {
+            self.message_bar.update_message(&warning);
+        }
+        self.views[self.active_view] = view;
+        self.arrange_panes();
+    }
+
+    // What is written to stdout on quitting: the first buffer, i.e. the one given on the
+    // command line unless it was closed
+    fn output(&self) -> Result<Vec<u8>, Error> {
+        self.buffer_views()
+            .first()
+            .map_or_else(|| Ok(Vec::new()), |index| self.views[*index].encode())
+    }
+
+    fn answer_recovery(&mut self, answer: &str) {
+        let Some(contents) = self.active_view().swap() else {
+            return;
+        };
+        match answer {
+            "r" | "recover" => {
+                self.active_view().recover(&contents);
+                self.message_bar
+                    .update_message("Recovered the unsaved changes, save to keep them.");
+            }
+            "x" | "discard" => {
+                let message = match self.active_view().discard_swap() {
+                    Ok(()) => "Discarded the swap file.".to_string(),
+                    Err(err) => format!("Could not remove the swap file: {err}"),
+                };
+                self.message_bar.update_message(&message);
+            }
+            "d" | "diff" => {
+                // The diff opens below, leaving the question about the file it belongs to
+                let diff = self.views[self.active_view].swap_diff(&contents);
+                self.show_below(View::from_text(&diff));
+                self.set_prompt(PromptType::Recover);
+            }
+            // The swap file is kept, and replaced once there are new changes
+            "" => {}
+            _ => self.set_prompt(PromptType::Recover),
+        }
+    }
+
+    fn reopen_with_encoding(&mut self, label: &str) {
+        let Some(encoding) = Encoding::for_label(label.as_bytes()) else {
+            self.message_bar
+                .update_message(&format!("Unknown encoding {label}."));
+            return;
+        };
+
+        let message = match self.active_view().reload_with_encoding(encoding) {
+            Ok(()) => self
+                .active_view()
+                .decode_warning()
+                .unwrap_or_else(|| format!("Reopened as {}.", encoding.name())),
+            Err(err) => format!("Could not reopen the file: {err}"),
+        };
+        self.message_bar.update_message(&message);
+    }
+
+    // Ask what to do if another program changed the file in the active pane. Returns whether
+    // it did, in which case the answer decides what happens next.
+    fn check_disk_changes(&mut self, then_save: bool) -> bool {
+        if self.prompt_type != PromptType::None || self.buffer_list.is_some() {
+            return false;
+        }
+        if !self.active_view().changed_on_disk() {
+            return false;
+        }
+        self.set_prompt(PromptType::Reload { then_save });
+        true
+    }
+
+    fn answer_disk_change(&mut self, answer: &str, then_save: bool) {
+        match answer {
+            "r" | "reload" => {
+                let message = match self.active_view().reload() {
+                    Ok(()) => "Reloaded the file from disk.".to_string(),
+                    Err(err) => format!("Could not reload the file: {err}"),
+                };
+                self.message_bar.update_message(&message);
+            }
+            "k" | "keep" => {
+                self.active_view().keep_over_disk();
+                if then_save {
+                    self.save();
+                } else {
+                    self.message_bar
+                        .update_message("Keeping your version, saving will replace the file.");
+                }
+            }
+            "d" | "diff" => {
+                let diff = match self.views[self.active_view].disk_diff() {
+                    Ok(diff) => diff,
+                    Err(err) => format!("Could not read the file: {err}"),
+                };
+                self.show_below(View::from_text(&diff));
+                self.set_prompt(PromptType::Reload { then_save });
+            }
+            _ => self.set_prompt(PromptType::Reload { then_save }),
+        }
+    }
+
+    // Open a view in a new pane under the active one, leaving the focus where it is
+    fn show_below(&mut self, view: View) {
+        self.views.push(view);
+        let new_view = self.views.len().saturating_sub(1);
+        self.layout
+            .split(self.active_view, new_view, SplitDirection::Horizontal);
+        self.arrange_panes();
+    }
+
+    fn save(&mut self) {
+        let Some(file_path) = self.views[self.active_view].file_path() else {
+            self.set_prompt(PromptType::SaveAs);
+            return;
+        };
+        if self.views[self.active_view].is_read_only() {
+            self.message_bar
+                .update_message("Buffer is read-only, Alt-A saves it under another name.");
+            return;
+        }
+        // A file that was never on disk may have been created by someone else since
+        if self.views[self.active_view].is_new_file() {
+            self.save_as(&file_path, false);
+            return;
+        }
+        // Don't overwrite changes another program made without asking
+        if self.check_disk_changes(true) {
+            return;
+        }
+        let keep_backups = self.keep_backups;
+        let message = match self.active_view().save(keep_backups) {
+            Ok(()) => "File saved successfully.".to_string(),
+            Err(err) => format!("Error writing file: {err}"),
+        };
+        self.message_bar.update_message(&message);
+    }
+
+    fn save_as(&mut self, file_path: &str, confirmed: bool) {
+        let path = Path::new(file_path);
+        if path.is_dir() {
+            self.message_bar
+                .update_message(&format!("{file_path} is a directory."));
+            return;
+        }
+        let active_view = &self.views[self.active_view];
+        if self.views.iter().any(|view| {
+            !view.shares_buffer_with(active_view) && view.file_path().as_deref() == Some(file_path)
+        }) {
+            self.message_bar
+                .update_message(&format!("{file_path} is open in another buffer."));
+            return;
+        }
+        // Only the file the buffer was read from is replaced without asking
+        let is_own_file =
+            active_view.file_path().as_deref() == Some(file_path) && !active_view.is_new_file();
+        if !confirmed && !is_own_file && path.exists() {
+            self.overwrite_path = Some(file_path.to_string());
+            self.set_prompt(PromptType::Overwrite);
+            return;
+        }
+
+        let keep_backups = self.keep_backups;
+        let message = match self.active_view().save_as(file_path, keep_backups) {
+            Ok(()) => format!("Saved as {file_path}."),
+            Err(err) => format!("Error writing file: {err}"),
+        };
+        self.message_bar.update_message(&message);
+    }
+
+    fn handle_close(&mut self, confirmed: bool) {
+        if self.views[self.active_view].is_modified() && !confirmed {
+            self.pending_confirmation = Confirmation::Close;
+            self.message_bar.update_message(
+                "WARNING! Buffer has unsaved changes. Press Ctrl-W again to close it anyway.",
+            );
+            return;
+        }
+
+        let _ = self.active_view().discard_swap();
+        let closing: Vec<usize> = (0..self.views.len())
+            .filter(|index| self.views[*index].shares_buffer_with(&self.views[self.active_view]))
+            .collect();
+        let Some(remaining) = (0..self.views.len()).find(|index| !closing.contains(index)) else {
+            // Closing the last buffer brings back the welcome screen
+            self.views = vec![View::default()];
+            self.layout = Layout::default();
+            self.active_view = 0;
+            self.arrange_panes();
+            return;
+        };
+
+        // Every pane showing the closed buffer switches to another one
+        let panes = self.layout.pane_views();
+        let active_pane = panes
+            .iter()
+            .position(|index| *index == self.active_view)
+            .unwrap_or(0);
+        for pane_view in panes {
+            if !closing.contains(&pane_view) {
+                continue;
+            }
+            let replacement = (0..self.views.len())
+                .find(|index| !closing.contains(index) && !self.layout.contains(*index))
+                .unwrap_or_else(|| {
+                    let view = self.views[remaining].split();
+                    self.views.push(view);
+                    self.views.len().saturating_sub(1)
+                });
+            self.layout.replace(pane_view, replacement);
+        }
+
+        for view_index in closing.into_iter().rev() {
+            self.remove_view(view_index);
+        }
+        self.active_view = self.layout.pane_views()[active_pane];
+        self.arrange_panes();
+    }
+
+    fn handle_quit(&mut self, confirmed: bool) {
+        if self.output_to_stdout {
+            if let Err(err) = self.output() {
+                self.message_bar
+                    .update_message(&format!("Could not write to stdout: {err}"));
+                return;
+            }
+        }
+        // Changes to the buffer going to stdout aren't lost
+        let output_view = self.buffer_views().first().copied();
+        let has_unsaved_changes = self.views.iter().any(|view| {
+            view.is_modified()
+                && !(self.output_to_stdout
+                    && output_view.is_some_and(|index| view.shares_buffer_with(&self.views[index])))
+        });
+        if has_unsaved_changes && !confirmed {
+            self.pending_confirmation = Confirmation::Quit;
+            self.message_bar.update_message(
+                "WARNING! There are unsaved changes. Press Ctrl-Q again to quit anyway.",
+            );
+            return;
+        }
+        // Unsaved changes were given up on, so there is nothing to recover later
+        for view in &mut self.views {
+            let _ = view.discard_swap();
         }
+        self.should_exit = true;
     }
 }
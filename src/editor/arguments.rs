@@ -0,0 +1,74 @@
+use super::view::{Indentation, WhitespaceGlyphs};
+
+// Command line options, then the file to open (`-` reads stdin)
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Default)]
+pub struct Arguments {
+    pub file_path: Option<String>,
+    // Columns taken by East Asian ambiguous-width characters (1 or 2)
+    pub ambiguous_width: Option<usize>,
+    // Measure how wide the terminal draws emoji and ambiguous characters at startup
+    pub probe_widths: bool,
+    // Start out showing whitespace, drawn with these glyphs
+    pub show_whitespace: bool,
+    pub whitespace_glyphs: WhitespaceGlyphs,
+    // Keep the previous contents of saved files as `file~`
+    pub backup: bool,
+    // Don't keep unsaved changes in swap files
+    pub no_swap: bool,
+    // Open the file for viewing only
+    pub readonly: bool,
+    // Write the buffer to stdout on exit, to use the editor in a pipeline
+    pub stdout: bool,
+    // Follow the file as it grows, like `tail -f`
+    pub follow: bool,
+    // Size in MiB from which files are opened in large-file mode
+    pub large_file: Option<u64>,
+    // One level of indentation, a tab or a number of spaces
+    pub indent: Option<Indentation>,
+}
+
+impl Arguments {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut arguments = Self::default();
+
+        for arg in args {
+            if let Some(width) = arg.strip_prefix("--ambiguous-width=") {
+                arguments.ambiguous_width = match width {
+                    "1" => Some(1),
+                    "2" => Some(2),
+                    _ => return Err(format!("Ambiguous width must be 1 or 2, not {width}")),
+                };
+            } else if arg == "--probe-widths" {
+                arguments.probe_widths = true;
+            } else if arg == "--show-whitespace" {
+                arguments.show_whitespace = true;
+            } else if arg == "--backup" {
+                arguments.backup = true;
+            } else if arg == "--no-swap" {
+                arguments.no_swap = true;
+            } else if arg == "--readonly" {
+                arguments.readonly = true;
+            } else if arg == "--stdout" {
+                arguments.stdout = true;
+            } else if arg == "--follow" {
+                arguments.follow = true;
+            } else if let Some(size) = arg.strip_prefix("--large-file=") {
+                arguments.large_file =
+                    Some(size.parse().map_err(|_| {
+                        format!("Large file size must be a number of MiB, not {size}")
+                    })?);
+            } else if let Some(spec) = arg.strip_prefix("--indent=") {
+                arguments.indent = Some(Indentation::parse(spec)?);
+            } else if let Some(spec) = arg.strip_prefix("--whitespace-glyphs=") {
+                arguments.whitespace_glyphs = WhitespaceGlyphs::parse(spec)?;
+            } else if arg.starts_with("--") {
+                return Err(format!("Unknown option {arg}"));
+            } else {
+                arguments.file_path = Some(arg);
+            }
+        }
+
+        Ok(arguments)
+    }
+}
@@ -0,0 +1,68 @@
+use super::command::{Command, Direction};
+use super::documentstatus::DocumentStatus;
+use super::terminal::{Rect, Terminal};
+
+// Selection popup listing every open buffer, rendered over the view area
+#[derive(Default)]
+pub struct BufferList {
+    entries: Vec<DocumentStatus>,
+    selected: usize,
+    needs_redraw: bool,
+}
+
+impl BufferList {
+    pub fn new(entries: Vec<DocumentStatus>, selected: usize) -> Self {
+        Self {
+            entries,
+            selected,
+            needs_redraw: true,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn handle_command(&mut self, command: &Command) {
+        let last_index = self.entries.len().saturating_sub(1);
+        self.selected = match command {
+            Command::Move(Direction::Up) => self.selected.saturating_sub(1),
+            Command::Move(Direction::Down) => self.selected.saturating_add(1).min(last_index),
+            Command::Move(Direction::PageUp | Direction::Home) => 0,
+            Command::Move(Direction::PageDown | Direction::End) => last_index,
+            _ => return,
+        };
+        self.needs_redraw = true;
+    }
+
+    pub fn render(&mut self, area: &Rect) {
+        if !self.needs_redraw {
+            return;
+        }
+
+        // Scroll the list so the selected entry always stays on screen
+        let scroll_offset = self
+            .selected
+            .saturating_sub(area.size.height.saturating_sub(1));
+        for curr_row in 0..area.size.height {
+            let entry_index = curr_row.saturating_add(scroll_offset);
+            match self.entries.get(entry_index) {
+                Some(entry) => {
+                    let row_string = format!(
+                        "{:>3}: {} {}",
+                        entry_index.saturating_add(1),
+                        entry.file_name_string(),
+                        entry.modified_indicator_string()
+                    );
+                    if entry_index == self.selected {
+                        Terminal::print_inverted_row(area, curr_row, &row_string);
+                    } else {
+                        Terminal::print_row(area, curr_row, &row_string);
+                    }
+                }
+                None => Terminal::print_row(area, curr_row, "~"),
+            }
+        }
+        self.needs_redraw = false;
+    }
+}
@@ -1,3 +1,4 @@
+use super::layout::SplitDirection;
 use super::terminal::Size;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
@@ -16,7 +17,33 @@ pub enum Command {
     Move(Direction),
     Insert(char),
     Remove,
+    RemoveCodePoint,
     Delete,
+    Enter,
+    Dismiss,
+    Save,
+    SaveAs,
+    Open,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    CloseBuffer,
+    Split(SplitDirection),
+    FocusNextPane,
+    ClosePane,
+    ResizePane(Direction),
+    Inspect,
+    ToggleWhitespace,
+    ConvertLineEndings,
+    ToggleFinalNewline,
+    ReopenWithEncoding,
+    ToggleReadOnly,
+    ToggleFollow,
+    Indent,
+    Outdent,
+    Undo,
+    Redo,
+    FindBytes,
     Resize(Size),
     Quit,
 }
@@ -36,7 +63,7 @@ impl TryFrom<KeyCode> for Direction {
             KeyCode::PageDown => Ok(Self::PageDown),
             KeyCode::Home => Ok(Self::Home),
             KeyCode::End => Ok(Self::End),
-            _ => Err(format!("Direction not supported!")),
+            _ => Err("Direction not supported!".to_string()),
         }
     }
 }
@@ -54,6 +81,42 @@ impl TryFrom<Event> for Command {
                 ..
             }) => match (code, modifiers) {
                 (KeyCode::Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
+                (KeyCode::Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
+                (KeyCode::Char('o'), KeyModifiers::CONTROL) => Ok(Self::Open),
+                (KeyCode::Char('n'), KeyModifiers::CONTROL) => Ok(Self::NextBuffer),
+                (KeyCode::Char('p'), KeyModifiers::CONTROL) => Ok(Self::PreviousBuffer),
+                (KeyCode::Char('b'), KeyModifiers::CONTROL) => Ok(Self::ListBuffers),
+                (KeyCode::Char('w'), KeyModifiers::CONTROL) => Ok(Self::CloseBuffer),
+                (KeyCode::Char('f'), KeyModifiers::CONTROL) => Ok(Self::FindBytes),
+                (KeyCode::Char('z'), KeyModifiers::CONTROL) => Ok(Self::Undo),
+                (KeyCode::Char('y'), KeyModifiers::CONTROL) => Ok(Self::Redo),
+                (KeyCode::Char('s'), KeyModifiers::ALT) => {
+                    Ok(Self::Split(SplitDirection::Horizontal))
+                }
+                (KeyCode::Char('v'), KeyModifiers::ALT) => {
+                    Ok(Self::Split(SplitDirection::Vertical))
+                }
+                (KeyCode::Char('o'), KeyModifiers::ALT) => Ok(Self::FocusNextPane),
+                (KeyCode::Char('c'), KeyModifiers::ALT) => Ok(Self::ClosePane),
+                (KeyCode::Char('i'), KeyModifiers::ALT) => Ok(Self::Inspect),
+                (KeyCode::Char('w'), KeyModifiers::ALT) => Ok(Self::ToggleWhitespace),
+                (KeyCode::Char('l'), KeyModifiers::ALT) => Ok(Self::ConvertLineEndings),
+                (KeyCode::Char('n'), KeyModifiers::ALT) => Ok(Self::ToggleFinalNewline),
+                (KeyCode::Char('e'), KeyModifiers::ALT) => Ok(Self::ReopenWithEncoding),
+                (KeyCode::Char('a'), KeyModifiers::ALT) => Ok(Self::SaveAs),
+                (KeyCode::Char('r'), KeyModifiers::ALT) => Ok(Self::ToggleReadOnly),
+                (KeyCode::Char('f'), KeyModifiers::ALT) => Ok(Self::ToggleFollow),
+                (KeyCode::Char('.'), KeyModifiers::ALT) => Ok(Self::Indent),
+                (KeyCode::Char(','), KeyModifiers::ALT) | (KeyCode::BackTab, _) => {
+                    Ok(Self::Outdent)
+                }
+                (
+                    KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down,
+                    KeyModifiers::ALT,
+                ) => match Direction::try_from(code) {
+                    Ok(direction) => Ok(Self::ResizePane(direction)),
+                    Err(err) => Err(err),
+                },
                 (
                     KeyCode::Left
                     | KeyCode::Right
@@ -66,12 +129,16 @@ impl TryFrom<Event> for Command {
                     _,
                 ) => match Direction::try_from(code) {
                     Ok(direction) => Ok(Self::Move(direction)),
-                    Err(err) => Err(format!("{err}")),
+                    Err(err) => Err(err),
                 },
                 (KeyCode::Char(c), _) => Ok(Self::Insert(c)),
+                (KeyCode::Tab, KeyModifiers::NONE) => Ok(Self::Insert('\t')),
+                (KeyCode::Backspace, KeyModifiers::ALT) => Ok(Self::RemoveCodePoint),
                 (KeyCode::Backspace, _) => Ok(Self::Remove),
                 (KeyCode::Delete, _) => Ok(Self::Delete),
-                _ => Err(format!("Code not supported!")),
+                (KeyCode::Enter, _) => Ok(Self::Enter),
+                (KeyCode::Esc, _) => Ok(Self::Dismiss),
+                _ => Err("Code not supported!".to_string()),
             },
             Event::Resize(width_u16, height_u16) => {
                 // Cast u16 to usize, and ignore clippy warnings
@@ -85,7 +152,7 @@ impl TryFrom<Event> for Command {
                     height: height_usize,
                 }))
             }
-            _ => Err(format!("Event not supported!")),
+            _ => Err("Event not supported!".to_string()),
         }
     }
 }
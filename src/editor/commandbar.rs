@@ -0,0 +1,92 @@
+use super::command::Command;
+use super::terminal::{Rect, Terminal};
+
+mod path_completion;
+
+#[derive(Default)]
+pub struct CommandBar {
+    prompt: String,
+    value: String,
+    // Paths Tab cycles through when completing could not get any further on its own
+    completions: Vec<String>,
+    completion_index: usize,
+    needs_redraw: bool,
+    area: Rect,
+}
+
+impl CommandBar {
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+        self.needs_redraw = true;
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.to_string();
+        self.completions.clear();
+        self.needs_redraw = true;
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn handle_command(&mut self, command: &Command) {
+        match command {
+            Command::Insert(char) if !char.is_control() => self.value.push(*char),
+            Command::Remove => {
+                self.value.pop();
+            }
+            _ => return,
+        }
+        self.completions.clear();
+        self.needs_redraw = true;
+    }
+
+    // Complete the value as a path as far as it is unambiguous. Once it isn't, pressing Tab
+    // again goes through the matching paths one by one.
+    pub fn complete_path(&mut self) {
+        if !self.completions.is_empty() {
+            self.completion_index = self
+                .completion_index
+                .saturating_add(1)
+                .checked_rem(self.completions.len())
+                .unwrap_or(0);
+            self.value
+                .clone_from(&self.completions[self.completion_index]);
+            self.needs_redraw = true;
+            return;
+        }
+
+        let candidates = path_completion::candidates(&self.value);
+        let prefix = path_completion::common_prefix(&candidates);
+        if prefix.len() > self.value.len() {
+            self.value = prefix;
+        } else if candidates.len() > 1 {
+            self.value.clone_from(&candidates[0]);
+            self.completions = candidates;
+            self.completion_index = 0;
+        }
+        self.needs_redraw = true;
+    }
+
+    pub fn caret_col(&self) -> usize {
+        self.prompt
+            .chars()
+            .count()
+            .saturating_add(self.value.chars().count())
+    }
+
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+        self.needs_redraw = true;
+    }
+
+    pub fn render(&mut self) {
+        if !self.needs_redraw {
+            return;
+        }
+
+        Terminal::print_row(&self.area, 0, &format!("{}{}", self.prompt, self.value));
+        self.needs_redraw = false;
+    }
+}
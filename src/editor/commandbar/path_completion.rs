@@ -0,0 +1,79 @@
+use std::{fs, path::MAIN_SEPARATOR};
+
+// Paths the typed one could be completed to: the entries of the directory typed so far whose
+// names start with the rest, sorted. Directories end with a separator, so completing goes on
+// inside them. Hidden entries only show up once a name starting with `.` is typed.
+pub fn candidates(typed: &str) -> Vec<String> {
+    let (directory, prefix) = match typed.rfind(['/', MAIN_SEPARATOR]) {
+        Some(index) => typed.split_at(index.saturating_add(1)),
+        None => ("", typed),
+    };
+    let Ok(entries) = fs::read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            // Follows links, so a link to a directory completes like one
+            let is_dir = fs::metadata(entry.path()).is_ok_and(|metadata| metadata.is_dir());
+            let separator = if is_dir { "/" } else { "" };
+            Some(format!("{directory}{name}{separator}"))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+// Longest start all the candidates have in common
+pub fn common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for candidate in rest {
+        let length = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or_else(
+                || prefix.len().min(candidate.len()),
+                |((index, _), _)| index,
+            );
+        prefix = &prefix[..length];
+    }
+    prefix.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{candidates, common_prefix};
+    use std::{env, fs, process};
+
+    #[test]
+    fn completes_names_inside_directories() {
+        let directory = env::temp_dir().join(format!("script-pad-{}-complete", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("notes")).unwrap();
+        fs::write(directory.join("notebook.txt"), b"").unwrap();
+        fs::write(directory.join("todo.txt"), b"").unwrap();
+        fs::write(directory.join(".hidden"), b"").unwrap();
+        let base = format!("{}/", directory.to_string_lossy());
+
+        let found = candidates(&format!("{base}no"));
+        assert_eq!(
+            found,
+            [format!("{base}notebook.txt"), format!("{base}notes/")]
+        );
+        assert_eq!(common_prefix(&found), format!("{base}note"));
+        assert_eq!(candidates(&format!("{base}t")), [format!("{base}todo.txt")]);
+        assert_eq!(candidates(&base).len(), 3);
+        assert_eq!(candidates(&format!("{base}.")), [format!("{base}.hidden")]);
+        assert!(candidates(&format!("{base}missing/")).is_empty());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
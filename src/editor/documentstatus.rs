@@ -0,0 +1,103 @@
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Default, Debug, PartialEq, Eq, Clone)]
+pub struct DocumentStatus {
+    pub file_name: Option<String>,
+    pub is_modified: bool,
+    pub total_lines: usize,
+    pub current_line: usize,
+    pub buffer_index: usize,
+    pub buffer_count: usize,
+    pub encoding: &'static str,
+    pub line_ending: &'static str,
+    pub has_bom: bool,
+    pub has_final_newline: bool,
+    pub is_binary: bool,
+    // Format the file is compressed in, if it is
+    pub compression: Option<&'static str>,
+    pub is_read_only: bool,
+    // Percentage of a large file whose lines were found, while that is still going on
+    pub indexing_progress: Option<usize>,
+    // Whether the view keeps up with the end of a followed file, or was scrolled away from it
+    pub following: Option<bool>,
+}
+
+impl DocumentStatus {
+    pub fn file_name_string(&self) -> String {
+        self.file_name
+            .clone()
+            .unwrap_or_else(|| "[No Name]".to_string())
+    }
+
+    pub fn modified_indicator_string(&self) -> String {
+        if self.is_modified {
+            "(modified)".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    // Following a file makes it read-only, which goes without saying then
+    pub fn read_only_indicator_string(&self) -> String {
+        if self.is_read_only && self.following.is_none() {
+            "[RO]".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    pub fn follow_indicator_string(&self) -> String {
+        match self.following {
+            Some(true) => "[follow]".to_string(),
+            Some(false) => "[paused]".to_string(),
+            None => String::new(),
+        }
+    }
+
+    pub fn position_indicator_string(&self) -> String {
+        format!(
+            "{}/{}",
+            self.current_line.saturating_add(1),
+            self.total_lines
+        )
+    }
+
+    // The compression format if there is one, then "binary" for files shown in the hex view,
+    // otherwise how far indexing a large file got, the encoding (unless it is UTF-8) and line
+    // ending style, then a byte order mark and a missing final newline if there are any
+    pub fn file_format_string(&self) -> String {
+        let mut format = self
+            .compression
+            .map(|compression| format!("{compression} "))
+            .unwrap_or_default();
+        if self.is_binary {
+            format.push_str("binary");
+            return format;
+        }
+        format.push_str(
+            &self
+                .indexing_progress
+                .map(|progress| format!("indexing {progress}% "))
+                .unwrap_or_default(),
+        );
+        if !self.encoding.is_empty() && self.encoding != "UTF-8" {
+            format.push_str(self.encoding);
+            format.push(' ');
+        }
+        format.push_str(self.line_ending);
+        if self.has_bom {
+            format.push_str(" BOM");
+        }
+        if !self.has_final_newline {
+            format.push_str(" noeol");
+        }
+        format
+    }
+
+    pub fn buffer_indicator_string(&self) -> String {
+        format!(
+            "[{}/{}]",
+            self.buffer_index.saturating_add(1),
+            self.buffer_count
+        )
+    }
+}
@@ -0,0 +1,250 @@
+use super::terminal::{Rect, Size};
+use super::view::position::Position;
+
+const MIN_RATIO: usize = 10;
+const MAX_RATIO: usize = 90;
+const RESIZE_STEP: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    // Panes stacked on top of each other
+    Horizontal,
+    // Panes placed side by side
+    Vertical,
+}
+
+// Tree of panes, where each leaf holds the index of the view rendered into it
+pub enum Layout {
+    Pane(usize),
+    Split {
+        direction: SplitDirection,
+        // Percentage of the available space given to the first child
+        ratio: usize,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+// The separator line drawn between the two children of a split
+pub struct Separator {
+    pub direction: SplitDirection,
+    pub area: Rect,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::Pane(0)
+    }
+}
+
+impl Layout {
+    // Compute the area of every pane (in depth-first order) and of every separator
+    pub fn arrange(
+        &self,
+        area: Rect,
+        panes: &mut Vec<(usize, Rect)>,
+        separators: &mut Vec<Separator>,
+    ) {
+        match self {
+            Self::Pane(view_index) => panes.push((*view_index, area)),
+            Self::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, separator_area, second_area) =
+                    Self::divide(area, *direction, *ratio);
+                first.arrange(first_area, panes, separators);
+                separators.push(Separator {
+                    direction: *direction,
+                    area: separator_area,
+                });
+                second.arrange(second_area, panes, separators);
+            }
+        }
+    }
+
+    fn divide(area: Rect, direction: SplitDirection, ratio: usize) -> (Rect, Rect, Rect) {
+        // One row/column of the area is taken up by the separator
+        let total = match direction {
+            SplitDirection::Horizontal => area.size.height,
+            SplitDirection::Vertical => area.size.width,
+        }
+        .saturating_sub(1);
+        #[allow(clippy::integer_division)]
+        let first_len = total.saturating_mul(ratio).saturating_div(100);
+        let second_len = total.saturating_sub(first_len);
+
+        match direction {
+            SplitDirection::Horizontal => (
+                Rect {
+                    origin: area.origin,
+                    size: Size {
+                        width: area.size.width,
+                        height: first_len,
+                    },
+                },
+                Rect {
+                    origin: Position {
+                        row: area.origin.row.saturating_add(first_len),
+                        col: area.origin.col,
+                    },
+                    size: Size {
+                        width: area.size.width,
+                        height: 1,
+                    },
+                },
+                Rect {
+                    origin: Position {
+                        row: area.origin.row.saturating_add(first_len).saturating_add(1),
+                        col: area.origin.col,
+                    },
+                    size: Size {
+                        width: area.size.width,
+                        height: second_len,
+                    },
+                },
+            ),
+            SplitDirection::Vertical => (
+                Rect {
+                    origin: area.origin,
+                    size: Size {
+                        width: first_len,
+                        height: area.size.height,
+                    },
+                },
+                Rect {
+                    origin: Position {
+                        row: area.origin.row,
+                        col: area.origin.col.saturating_add(first_len),
+                    },
+                    size: Size {
+                        width: 1,
+                        height: area.size.height,
+                    },
+                },
+                Rect {
+                    origin: Position {
+                        row: area.origin.row,
+                        col: area.origin.col.saturating_add(first_len).saturating_add(1),
+                    },
+                    size: Size {
+                        width: second_len,
+                        height: area.size.height,
+                    },
+                },
+            ),
+        }
+    }
+
+    pub fn pane_views(&self) -> Vec<usize> {
+        let mut panes = Vec::new();
+        self.arrange(Rect::default(), &mut panes, &mut Vec::new());
+        panes
+            .into_iter()
+            .map(|(view_index, _)| view_index)
+            .collect()
+    }
+
+    pub fn contains(&self, view_index: usize) -> bool {
+        match self {
+            Self::Pane(index) => *index == view_index,
+            Self::Split { first, second, .. } => {
+                first.contains(view_index) || second.contains(view_index)
+            }
+        }
+    }
+
+    // Split the pane showing `view_index`, placing `new_view_index` after it
+    pub fn split(&mut self, view_index: usize, new_view_index: usize, direction: SplitDirection) {
+        match self {
+            Self::Pane(index) if *index == view_index => {
+                *self = Self::Split {
+                    direction,
+                    ratio: 50,
+                    first: Box::new(Self::Pane(view_index)),
+                    second: Box::new(Self::Pane(new_view_index)),
+                };
+            }
+            Self::Pane(_) => {}
+            Self::Split { first, second, .. } => {
+                first.split(view_index, new_view_index, direction);
+                second.split(view_index, new_view_index, direction);
+            }
+        }
+    }
+
+    // Remove the pane showing `view_index`, letting its sibling take over the freed space
+    pub fn remove(&mut self, view_index: usize) {
+        let Self::Split { first, second, .. } = self else {
+            return;
+        };
+
+        let sibling = if matches!(**first, Self::Pane(index) if index == view_index) {
+            std::mem::take(second)
+        } else if matches!(**second, Self::Pane(index) if index == view_index) {
+            std::mem::take(first)
+        } else {
+            first.remove(view_index);
+            second.remove(view_index);
+            return;
+        };
+        *self = *sibling;
+    }
+
+    // Grow (or shrink) the pane showing `view_index` along the closest split of the given direction.
+    // Returns whether such a split was found.
+    pub fn resize(&mut self, view_index: usize, direction: SplitDirection, grow: bool) -> bool {
+        let Self::Split {
+            direction: split_direction,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return false;
+        };
+
+        let in_first = first.contains(view_index);
+        if !in_first && !second.contains(view_index) {
+            return false;
+        }
+
+        // Prefer the innermost matching split
+        let child = if in_first { first } else { second };
+        if child.resize(view_index, direction, grow) {
+            return true;
+        }
+        if *split_direction != direction {
+            return false;
+        }
+
+        *ratio = if grow == in_first {
+            ratio.saturating_add(RESIZE_STEP).min(MAX_RATIO)
+        } else {
+            ratio.saturating_sub(RESIZE_STEP).max(MIN_RATIO)
+        };
+        true
+    }
+
+    pub fn replace(&mut self, old_view_index: usize, new_view_index: usize) {
+        self.update_views(&|index| {
+            if index == old_view_index {
+                new_view_index
+            } else {
+                index
+            }
+        });
+    }
+
+    pub fn update_views(&mut self, update: &dyn Fn(usize) -> usize) {
+        match self {
+            Self::Pane(index) => *index = update(*index),
+            Self::Split { first, second, .. } => {
+                first.update_views(update);
+                second.update_views(update);
+            }
+        }
+    }
+}
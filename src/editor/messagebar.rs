@@ -0,0 +1,65 @@
+use super::terminal::{Rect, Terminal};
+use std::time::{Duration, Instant};
+
+const MESSAGE_DURATION: Duration = Duration::new(5, 0);
+
+pub struct MessageBar {
+    message: String,
+    set_time: Instant,
+    needs_redraw: bool,
+    area: Rect,
+    cleared_after_expiry: bool,
+}
+
+impl Default for MessageBar {
+    fn default() -> Self {
+        Self {
+            message: String::new(),
+            set_time: Instant::now(),
+            needs_redraw: true,
+            cleared_after_expiry: false,
+            area: Rect::default(),
+        }
+    }
+}
+
+impl MessageBar {
+    pub fn update_message(&mut self, new_message: &str) {
+        self.message = new_message.to_string();
+        self.set_time = Instant::now();
+        self.needs_redraw = true;
+        self.cleared_after_expiry = false;
+    }
+
+    pub fn set_needs_redraw(&mut self) {
+        self.needs_redraw = true;
+    }
+
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+        self.needs_redraw = true;
+    }
+
+    fn is_expired(&self) -> bool {
+        Instant::now().duration_since(self.set_time) > MESSAGE_DURATION
+    }
+
+    pub fn render(&mut self) {
+        // Once a message expires, redraw the bar a single time to clear it
+        if self.is_expired() && !self.cleared_after_expiry {
+            self.needs_redraw = true;
+        }
+        if !self.needs_redraw {
+            return;
+        }
+
+        let message = if self.is_expired() {
+            self.cleared_after_expiry = true;
+            ""
+        } else {
+            &self.message
+        };
+        Terminal::print_row(&self.area, 0, message);
+        self.needs_redraw = false;
+    }
+}
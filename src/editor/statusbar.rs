@@ -0,0 +1,64 @@
+use super::documentstatus::DocumentStatus;
+use super::terminal::{Rect, Terminal};
+
+#[derive(Default)]
+pub struct StatusBar {
+    current_status: DocumentStatus,
+    needs_redraw: bool,
+    area: Rect,
+}
+
+impl StatusBar {
+    pub fn update_status(&mut self, new_status: DocumentStatus) {
+        if new_status != self.current_status {
+            self.current_status = new_status;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+        self.needs_redraw = true;
+    }
+
+    pub fn render(&mut self) {
+        if !self.needs_redraw {
+            return;
+        }
+
+        let left = [
+            self.current_status.buffer_indicator_string(),
+            self.current_status.file_name_string(),
+            self.current_status.read_only_indicator_string(),
+            self.current_status.follow_indicator_string(),
+            self.current_status.modified_indicator_string(),
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ");
+        let position = self.current_status.position_indicator_string();
+        let format_and_position =
+            format!("{} {position}", self.current_status.file_format_string());
+
+        // Right-align the file format and position, dropping the format first and then the
+        // position if the bar is too narrow
+        let remaining = self
+            .area
+            .size
+            .width
+            .saturating_sub(left.chars().count())
+            .saturating_sub(1);
+        let status = if let Some(right) = [format_and_position, position]
+            .into_iter()
+            .find(|right| remaining >= right.len())
+        {
+            format!("{left} {right:>remaining$}")
+        } else {
+            left
+        };
+
+        Terminal::print_inverted_row(&self.area, 0, &status);
+        self.needs_redraw = false;
+    }
+}
@@ -1,106 +1,228 @@
 use super::view::position::Position;
-use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    queue,
-    style::Print,
-    terminal::{
-        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
-        LeaveAlternateScreen,
-    },
-    Command,
-};
-use std::io::{stdout, Error, Write};
-
-#[derive(Default, Debug, Copy, Clone)]
+pub use backend::Backend;
+use backend::CrosstermBackend;
+use crossterm::event::Event;
+use frame::Frame;
+pub use frame::Style;
+use std::cell::RefCell;
+use std::io::Error;
+use std::time::Duration;
+
+pub use width::{grapheme_width, WidthModel};
+
+#[cfg(test)]
+pub use backend::MemoryBackend;
+
+mod backend;
+mod frame;
+mod width;
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Size {
     pub width: usize,
     pub height: usize,
 }
 
+// A sub-region of the terminal, with rows and columns printed into it being relative to its origin
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub origin: Position,
+    pub size: Size,
+}
+
+impl Rect {
+    pub fn row(row: usize, width: usize) -> Self {
+        Self {
+            origin: Position { row, col: 0 },
+            size: Size { width, height: 1 },
+        }
+    }
+
+    pub fn to_screen(self, pos: &Position) -> Position {
+        Position {
+            row: self.origin.row.saturating_add(pos.row),
+            col: self.origin.col.saturating_add(pos.col),
+        }
+    }
+}
+
+// What is about to be shown on screen, compared against what was sent with the last frame
+struct Screen {
+    backend: Box<dyn Backend>,
+    previous: Option<Frame>,
+    current: Frame,
+    caret: Position,
+    caret_visible: bool,
+}
+
+impl Screen {
+    fn new(backend: Box<dyn Backend>) -> Self {
+        Self {
+            backend,
+            previous: None,
+            current: Frame::default(),
+            caret: Position::default(),
+            caret_visible: false,
+        }
+    }
+}
+
+thread_local! {
+    static SCREEN: RefCell<Screen> = RefCell::new(Screen::new(Box::new(CrosstermBackend::default())));
+}
+
 pub struct Terminal {}
 
 impl Terminal {
+    // Draw onto something other than the real terminal from now on
+    #[cfg(test)]
+    pub fn set_backend(backend: Box<dyn Backend>) {
+        SCREEN.with_borrow_mut(|screen| *screen = Screen::new(backend));
+    }
+
     pub fn initialize() -> Result<(), Error> {
-        enable_raw_mode()?;
-        let _ = Self::enter_alternate_screen();
-        Self::clear_screen()?;
-        Self::move_caret_to(&Position { row: 0, col: 0 })?;
+        SCREEN.with_borrow_mut(|screen| screen.backend.initialize())?;
+        Self::resize(Self::size()?);
+        Self::move_caret_to(&Position { row: 0, col: 0 });
         Self::execute()?;
         Ok(())
     }
 
     pub fn terminate() -> Result<(), Error> {
-        let _ = Self::leave_alternate_screen();
-        let _ = Self::show_caret();
-        Self::execute()?;
-        disable_raw_mode()?;
-        Ok(())
+        // This also runs from the panic hook, possibly while the screen is being drawn
+        SCREEN.with(|screen| match screen.try_borrow_mut() {
+            Ok(mut screen) => screen.backend.terminate(),
+            Err(_) => CrosstermBackend::default().terminate(),
+        })
     }
 
-    pub fn queue_command<T: Command>(command: T) -> Result<(), Error> {
-        queue!(stdout(), command)?;
-        Ok(())
+    // Start over with a blank back-buffer of the new size, repainting the whole screen next frame
+    pub fn resize(size: Size) {
+        SCREEN.with_borrow_mut(|screen| {
+            screen.previous = None;
+            screen.current = Frame::new(size);
+        });
     }
 
-    pub fn enter_alternate_screen() -> Result<(), Error> {
-        Self::queue_command(EnterAlternateScreen)?;
-        Ok(())
+    // Send everything that changed since the last frame in a single write
+    pub fn execute() -> Result<(), Error> {
+        SCREEN.with_borrow_mut(|screen| {
+            let Screen {
+                backend,
+                previous,
+                current,
+                caret,
+                caret_visible,
+            } = screen;
+
+            backend.set_cursor_visible(false)?;
+            current.write_diff(previous.as_ref(), backend.as_mut())?;
+            backend.move_cursor(caret)?;
+            if *caret_visible {
+                backend.set_cursor_visible(true)?;
+            }
+            *previous = Some(current.clone());
+            backend.flush()
+        })
     }
 
-    pub fn leave_alternate_screen() -> Result<(), Error> {
-        Self::queue_command(LeaveAlternateScreen)?;
-        Ok(())
+    // Ask the terminal how wide it draws a few sample graphemes, and fit the width model to that
+    pub fn calibrate_widths() -> Result<WidthModel, Error> {
+        let model = SCREEN.with_borrow_mut(|screen| {
+            let mut measure = |sample: &str| -> Result<usize, Error> {
+                screen.backend.move_cursor(&Position::default())?;
+                screen.backend.print(sample)?;
+                Ok(screen.backend.cursor_position()?.col)
+            };
+            let model = WidthModel::calibrated(
+                measure(width::AMBIGUOUS_SAMPLE)?,
+                measure(width::EMOJI_SEQUENCE_SAMPLE)?,
+                measure(width::EMOJI_PRESENTATION_SAMPLE)?,
+            );
+
+            // The samples were drawn over whatever was on screen
+            screen.backend.clear()?;
+            screen.previous = None;
+            Ok::<_, Error>(model)
+        })?;
+        WidthModel::set_current(model);
+        Ok(model)
     }
 
-    pub fn clear_screen() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::All))?;
-        Ok(())
+    pub fn size() -> Result<Size, Error> {
+        SCREEN.with_borrow(|screen| screen.backend.size())
     }
 
-    pub fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::CurrentLine))?;
-        Ok(())
+    pub fn read_event() -> Result<Event, Error> {
+        SCREEN.with_borrow_mut(|screen| screen.backend.read_event())
     }
 
-    pub fn execute() -> Result<(), Error> {
-        stdout().flush()?;
-        Ok(())
+    pub fn poll_event(timeout: Duration) -> Result<bool, Error> {
+        SCREEN.with_borrow_mut(|screen| screen.backend.poll_event(timeout))
     }
 
-    pub fn size() -> Result<Size, Error> {
-        let (width, height) = crossterm::terminal::size()?;
-        #[allow(clippy::as_conversions)]
-        Ok(Size {
-            width: width as usize,
-            height: height as usize,
+    // Print straight to the terminal, bypassing the back-buffer
+    pub fn print(str: &str) -> Result<(), Error> {
+        SCREEN.with_borrow_mut(|screen| {
+            screen.backend.print(str)?;
+            screen.backend.flush()
         })
     }
 
-    pub fn print(str: &str) -> Result<(), Error> {
-        Self::queue_command(Print(str))?;
-        Ok(())
+    // Print a row inside an area, padding it with blanks so nothing outside the area is touched
+    pub fn print_row(area: &Rect, row: usize, str: &str) {
+        Self::print_styled_row(area, row, str, Style::default());
     }
 
-    pub fn print_row(row: usize, str: &str) -> Result<(), Error> {
-        Self::move_caret_to(&Position { row, col: 0 })?;
-        Self::clear_line()?;
-        Self::queue_command(Print(str))?;
-        Ok(())
+    pub fn print_inverted_row(area: &Rect, row: usize, str: &str) {
+        Self::print_styled_row(
+            area,
+            row,
+            str,
+            Style {
+                reverse: true,
+                ..Style::default()
+            },
+        );
     }
 
-    pub fn move_caret_to(pos: &Position) -> Result<(), Error> {
-        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
-        Self::queue_command(MoveTo(pos.col as u16, pos.row as u16))?;
-        Ok(())
+    fn print_styled_row(area: &Rect, row: usize, str: &str, style: Style) {
+        if row >= area.size.height {
+            return;
+        }
+        SCREEN.with_borrow_mut(|screen| {
+            screen.current.set_row(
+                area.to_screen(&Position { row, col: 0 }),
+                area.size.width,
+                str,
+                style,
+            );
+        });
     }
 
-    pub fn hide_caret() -> Result<(), Error> {
-        Self::queue_command(Hide)?;
-        Ok(())
+    // Print a row made of differently styled pieces, padding it with blanks like `print_row`
+    pub fn print_spans(area: &Rect, row: usize, spans: &[(String, Style)]) {
+        if row >= area.size.height {
+            return;
+        }
+        SCREEN.with_borrow_mut(|screen| {
+            screen.current.set_spans(
+                area.to_screen(&Position { row, col: 0 }),
+                area.size.width,
+                spans,
+            );
+        });
     }
 
-    pub fn show_caret() -> Result<(), Error> {
-        Self::queue_command(Show)?;
-        Ok(())
+    pub fn move_caret_to(pos: &Position) {
+        SCREEN.with_borrow_mut(|screen| screen.caret = *pos);
+    }
+
+    pub fn hide_caret() {
+        SCREEN.with_borrow_mut(|screen| screen.caret_visible = false);
+    }
+
+    pub fn show_caret() {
+        SCREEN.with_borrow_mut(|screen| screen.caret_visible = true);
     }
 }
@@ -0,0 +1,37 @@
+use super::frame::Style;
+use super::{Position, Size};
+use crossterm::event::{poll, read, Event};
+use std::{io::Error, time::Duration};
+
+pub use crossterm_backend::CrosstermBackend;
+#[cfg(test)]
+pub use memory_backend::MemoryBackend;
+
+mod crossterm_backend;
+#[cfg(test)]
+mod memory_backend;
+
+// Everything the editor needs from the terminal it runs in, so it can also be driven
+// without a real TTY
+pub trait Backend {
+    // Prepare the terminal for drawing (raw mode, alternate screen)
+    fn initialize(&mut self) -> Result<(), Error>;
+    fn terminate(&mut self) -> Result<(), Error>;
+    fn size(&self) -> Result<Size, Error>;
+    fn clear(&mut self) -> Result<(), Error>;
+    fn move_cursor(&mut self, pos: &Position) -> Result<(), Error>;
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error>;
+    fn set_style(&mut self, style: Style) -> Result<(), Error>;
+    // Print at the cursor, moving it past the printed text
+    fn print(&mut self, str: &str) -> Result<(), Error>;
+    fn flush(&mut self) -> Result<(), Error>;
+    // Where the terminal reports its cursor to be, after flushing pending output
+    fn cursor_position(&mut self) -> Result<Position, Error>;
+    fn read_event(&mut self) -> Result<Event, Error> {
+        read()
+    }
+    // Wait up to `timeout` for an event, returning whether one can be read without blocking
+    fn poll_event(&mut self, timeout: Duration) -> Result<bool, Error> {
+        poll(timeout)
+    }
+}
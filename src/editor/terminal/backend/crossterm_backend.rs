@@ -0,0 +1,136 @@
+use super::super::frame::Style;
+use super::super::{Position, Size};
+use super::Backend;
+use crossterm::{
+    cursor::{position, Hide, MoveTo, Show},
+    event::{DisableFocusChange, EnableFocusChange},
+    queue,
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{stdout, Error, ErrorKind, IsTerminal, Write},
+};
+
+// Draws onto the real terminal through crossterm. Output is collected until
+// `flush`, so that a whole frame goes out in a single write.
+pub struct CrosstermBackend {
+    output: Vec<u8>,
+    // The terminal itself, when stdout is redirected (e.g. the editor is part of a pipeline).
+    // Keys come from it as well then, which crossterm takes care of.
+    tty: Option<File>,
+}
+
+impl Default for CrosstermBackend {
+    fn default() -> Self {
+        let tty = if stdout().is_terminal() {
+            None
+        } else {
+            OpenOptions::new().write(true).open("/dev/tty").ok()
+        };
+        Self {
+            output: Vec::new(),
+            tty,
+        }
+    }
+}
+
+impl Backend for CrosstermBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        enable_raw_mode()?;
+        // Focus events tell when to look for changes other programs made to open files
+        queue!(self.output, EnterAlternateScreen, EnableFocusChange)?;
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        queue!(self.output, DisableFocusChange, LeaveAlternateScreen, Show)?;
+        self.flush()?;
+        disable_raw_mode()?;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        let (width, height) = size()?;
+        #[allow(clippy::as_conversions)]
+        Ok(Size {
+            width: width as usize,
+            height: height as usize,
+        })
+    }
+
+    fn clear(&mut self) -> Result<(), Error> {
+        queue!(self.output, Clear(ClearType::All))?;
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, pos: &Position) -> Result<(), Error> {
+        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+        queue!(self.output, MoveTo(pos.col as u16, pos.row as u16))?;
+        Ok(())
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error> {
+        if visible {
+            queue!(self.output, Show)?;
+        } else {
+            queue!(self.output, Hide)?;
+        }
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: Style) -> Result<(), Error> {
+        queue!(self.output, SetAttribute(Attribute::Reset))?;
+        if style.reverse {
+            queue!(self.output, SetAttribute(Attribute::Reverse))?;
+        }
+        if style.dim {
+            queue!(self.output, SetAttribute(Attribute::Dim))?;
+        }
+        if let Some(color) = style.foreground {
+            queue!(self.output, SetForegroundColor(color))?;
+        }
+        if let Some(color) = style.background {
+            queue!(self.output, SetBackgroundColor(color))?;
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, str: &str) -> Result<(), Error> {
+        queue!(self.output, Print(str))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if let Some(tty) = &mut self.tty {
+            tty.write_all(&self.output)?;
+            tty.flush()?;
+        } else {
+            let mut stdout = stdout();
+            stdout.write_all(&self.output)?;
+            stdout.flush()?;
+        }
+        self.output.clear();
+        Ok(())
+    }
+
+    fn cursor_position(&mut self) -> Result<Position, Error> {
+        // crossterm sends the query for it to stdout, where the terminal wouldn't see it
+        if self.tty.is_some() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "the cursor position can't be asked for while stdout is redirected",
+            ));
+        }
+        self.flush()?;
+        let (col, row) = position()?;
+        Ok(Position {
+            row: row.into(),
+            col: col.into(),
+        })
+    }
+}
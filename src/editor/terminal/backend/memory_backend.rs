@@ -0,0 +1,138 @@
+use super::super::frame::{Cell, Frame, Style};
+use super::super::width::text_width;
+use super::super::{Position, Size};
+use super::Backend;
+use crossterm::event::Event;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{Error, ErrorKind},
+    rc::Rc,
+    time::Duration,
+};
+
+#[derive(Default)]
+struct MemoryScreen {
+    cells: Frame,
+    cursor: Position,
+    cursor_visible: bool,
+    style: Style,
+    events: VecDeque<Event>,
+    // Whether the editor was already told there are no more events, see `poll_event`
+    reported_idle: bool,
+}
+
+// Records what would be drawn into a grid of cells instead of a TTY, and replays queued
+// events. Clones share the same screen, so a test can keep one to inspect the output.
+#[derive(Clone)]
+pub struct MemoryBackend {
+    screen: Rc<RefCell<MemoryScreen>>,
+}
+
+impl MemoryBackend {
+    pub fn new(size: Size) -> Self {
+        Self {
+            screen: Rc::new(RefCell::new(MemoryScreen {
+                cells: Frame::new(size),
+                ..MemoryScreen::default()
+            })),
+        }
+    }
+
+    pub fn push_event(&self, event: Event) {
+        let mut screen = self.screen.borrow_mut();
+        screen.events.push_back(event);
+        screen.reported_idle = false;
+    }
+
+    // Text shown on a row, without trailing blanks
+    pub fn row(&self, row: usize) -> String {
+        self.screen
+            .borrow()
+            .cells
+            .row_text(row)
+            .trim_end()
+            .to_string()
+    }
+
+    pub fn cell(&self, pos: &Position) -> Option<Cell> {
+        self.screen.borrow().cells.cell(pos).cloned()
+    }
+
+    pub fn cursor(&self) -> Option<Position> {
+        let screen = self.screen.borrow();
+        screen.cursor_visible.then_some(screen.cursor)
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        Ok(self.screen.borrow().cells.size())
+    }
+
+    fn clear(&mut self) -> Result<(), Error> {
+        let mut screen = self.screen.borrow_mut();
+        screen.cells = Frame::new(screen.cells.size());
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, pos: &Position) -> Result<(), Error> {
+        self.screen.borrow_mut().cursor = *pos;
+        Ok(())
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error> {
+        self.screen.borrow_mut().cursor_visible = visible;
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: Style) -> Result<(), Error> {
+        self.screen.borrow_mut().style = style;
+        Ok(())
+    }
+
+    fn print(&mut self, str: &str) -> Result<(), Error> {
+        let mut screen = self.screen.borrow_mut();
+        let (cursor, style, width) = (screen.cursor, screen.style, text_width(str));
+        screen.cells.set_row(cursor, width, str, style);
+        screen.cursor.col = cursor.col.saturating_add(width);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn cursor_position(&mut self) -> Result<Position, Error> {
+        Ok(self.screen.borrow().cursor)
+    }
+
+    // Once a script has run out, the editor is idle for one poll (as if the user paused)
+    // before reading runs into the end of the events
+    fn poll_event(&mut self, _timeout: Duration) -> Result<bool, Error> {
+        let mut screen = self.screen.borrow_mut();
+        if screen.events.is_empty() && !screen.reported_idle {
+            screen.reported_idle = true;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn read_event(&mut self) -> Result<Event, Error> {
+        let mut screen = self.screen.borrow_mut();
+        let event = screen.events.pop_front();
+        // The next script, even an empty one, starts with a pause again
+        if event.is_none() {
+            screen.reported_idle = false;
+        }
+        event.ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "No more events"))
+    }
+}
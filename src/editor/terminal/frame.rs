@@ -0,0 +1,203 @@
+use super::backend::Backend;
+use super::width::grapheme_width;
+use super::{Position, Size};
+use crossterm::style::Color;
+use std::{io::Error, ops::Range};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Style {
+    pub reverse: bool,
+    pub dim: bool,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+// A single terminal cell. The cell following a wide grapheme holds an empty symbol,
+// since it is covered by the grapheme before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: String,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".to_string(),
+            style: Style::default(),
+        }
+    }
+}
+
+// Back-buffer holding the contents of the whole screen, so a new frame can be compared with
+// the previous one and only the cells that changed get sent to the terminal
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    size: Size,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::default(); size.width.saturating_mul(size.height)],
+        }
+    }
+
+    #[cfg(test)]
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    // Text of a whole row, as it would appear on screen
+    #[cfg(test)]
+    pub fn row_text(&self, row: usize) -> String {
+        (0..self.size.width)
+            .filter_map(|col| self.cell(&Position { row, col }))
+            .map(|cell| cell.symbol.as_str())
+            .collect()
+    }
+
+    pub fn cell(&self, pos: &Position) -> Option<&Cell> {
+        if pos.col >= self.size.width {
+            return None;
+        }
+        self.cells.get(
+            pos.row
+                .saturating_mul(self.size.width)
+                .saturating_add(pos.col),
+        )
+    }
+
+    fn cell_mut(&mut self, pos: &Position) -> Option<&mut Cell> {
+        if pos.col >= self.size.width {
+            return None;
+        }
+        self.cells.get_mut(
+            pos.row
+                .saturating_mul(self.size.width)
+                .saturating_add(pos.col),
+        )
+    }
+
+    // Write `text` starting at `pos`, filling the rest of `width` columns with blanks
+    pub fn set_row(&mut self, pos: Position, width: usize, text: &str, style: Style) {
+        let end_col = pos.col.saturating_add(width).min(self.size.width);
+        let col = self.set_text(pos, end_col, text, style);
+        self.fill(pos.row, col..end_col, style);
+    }
+
+    // Like `set_row`, but with each piece of the text in its own style
+    pub fn set_spans(&mut self, pos: Position, width: usize, spans: &[(String, Style)]) {
+        let end_col = pos.col.saturating_add(width).min(self.size.width);
+        let mut col = pos.col;
+        for (text, style) in spans {
+            col = self.set_text(Position { row: pos.row, col }, end_col, text, *style);
+        }
+        self.fill(pos.row, col..end_col, Style::default());
+    }
+
+    // Write as much of `text` as fits before `end_col`, returning the column after it
+    fn set_text(&mut self, pos: Position, end_col: usize, text: &str, style: Style) -> usize {
+        let mut col = pos.col;
+
+        for grapheme in text.graphemes(true) {
+            let grapheme_width = grapheme_width(grapheme);
+            // Zero-width graphemes are combined with the cell before them
+            if grapheme_width == 0 {
+                if let Some(cell) = col.checked_sub(1).and_then(|prev_col| {
+                    self.cell_mut(&Position {
+                        row: pos.row,
+                        col: prev_col,
+                    })
+                }) {
+                    cell.symbol.push_str(grapheme);
+                }
+                continue;
+            }
+
+            let cell_count = grapheme_width;
+            if col.saturating_add(cell_count) > end_col {
+                break;
+            }
+            for offset in 0..cell_count {
+                if let Some(cell) = self.cell_mut(&Position {
+                    row: pos.row,
+                    col: col.saturating_add(offset),
+                }) {
+                    *cell = Cell {
+                        symbol: if offset == 0 {
+                            grapheme.to_string()
+                        } else {
+                            String::new()
+                        },
+                        style,
+                    };
+                }
+            }
+            col = col.saturating_add(cell_count);
+        }
+
+        col
+    }
+
+    fn fill(&mut self, row: usize, cols: Range<usize>, style: Style) {
+        for blank_col in cols {
+            if let Some(cell) = self.cell_mut(&Position {
+                row,
+                col: blank_col,
+            }) {
+                *cell = Cell {
+                    symbol: " ".to_string(),
+                    style,
+                };
+            }
+        }
+    }
+
+    // Send what changed between `previous` and this frame to the backend. Without a
+    // previous frame (e.g. after a resize) the whole screen is repainted.
+    pub fn write_diff(
+        &self,
+        previous: Option<&Self>,
+        backend: &mut dyn Backend,
+    ) -> Result<(), Error> {
+        let previous = previous.filter(|previous| previous.size == self.size);
+        if previous.is_none() {
+            backend.clear()?;
+        }
+
+        let mut current_style = Style::default();
+        backend.set_style(current_style)?;
+        for row in 0..self.size.height {
+            // Where the terminal cursor is after the last print, to skip redundant moves
+            let mut cursor: Option<usize> = None;
+            for col in 0..self.size.width {
+                let pos = Position { row, col };
+                let Some(cell) = self.cell(&pos) else {
+                    continue;
+                };
+                let unchanged = previous
+                    .and_then(|previous| previous.cell(&pos))
+                    .is_some_and(|previous_cell| previous_cell == cell);
+                if unchanged || cell.symbol.is_empty() {
+                    continue;
+                }
+
+                if cursor != Some(col) {
+                    backend.move_cursor(&pos)?;
+                }
+                if cell.style != current_style {
+                    backend.set_style(cell.style)?;
+                    current_style = cell.style;
+                }
+                backend.print(&cell.symbol)?;
+                cursor = Some(col.saturating_add(grapheme_width(&cell.symbol).max(1)));
+            }
+        }
+        backend.set_style(Style::default())?;
+        Ok(())
+    }
+}
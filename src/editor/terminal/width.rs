@@ -0,0 +1,187 @@
+use std::cell::Cell;
+use unicode_width::UnicodeWidthStr;
+
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+const TEXT_PRESENTATION: char = '\u{fe0e}';
+const EMOJI_PRESENTATION: char = '\u{fe0f}';
+
+// Graphemes the terminal is asked to draw when calibrating, one per setting of the model
+pub const AMBIGUOUS_SAMPLE: &str = "\u{a1}";
+pub const EMOJI_SEQUENCE_SAMPLE: &str = "\u{1f469}\u{200d}\u{1f467}";
+pub const EMOJI_PRESENTATION_SAMPLE: &str = "\u{263a}\u{fe0f}";
+
+// How many columns the terminal takes to draw a grapheme. Terminals disagree on a few
+// kinds of characters, so those are settings rather than being hard-coded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WidthModel {
+    // Columns taken by East Asian ambiguous-width characters, 1 or 2
+    pub ambiguous_width: usize,
+    // Whether ZWJ and skin tone sequences are drawn as a single emoji instead of one per part
+    pub joins_emoji_sequences: bool,
+    // Whether a text character followed by VS16 (e.g. "☺️") is drawn as a wide emoji
+    pub emoji_presentation: bool,
+}
+
+impl Default for WidthModel {
+    fn default() -> Self {
+        Self {
+            ambiguous_width: 1,
+            joins_emoji_sequences: true,
+            emoji_presentation: true,
+        }
+    }
+}
+
+thread_local! {
+    static MODEL: Cell<WidthModel> = Cell::new(WidthModel::default());
+}
+
+impl WidthModel {
+    pub fn current() -> Self {
+        MODEL.get()
+    }
+
+    pub fn set_current(model: Self) {
+        MODEL.set(model);
+    }
+
+    // Derive the settings from how wide the terminal drew each of the samples
+    pub fn calibrated(ambiguous: usize, emoji_sequence: usize, emoji_presentation: usize) -> Self {
+        Self {
+            ambiguous_width: ambiguous.clamp(1, 2),
+            joins_emoji_sequences: emoji_sequence == 2,
+            emoji_presentation: emoji_presentation == 2,
+        }
+    }
+
+    pub fn grapheme_width(&self, grapheme: &str) -> usize {
+        // Control characters are not drawn at all
+        if grapheme.chars().next().is_none_or(char::is_control) {
+            return 0;
+        }
+
+        let text = if self.emoji_presentation {
+            grapheme.to_string()
+        } else {
+            grapheme.replace(EMOJI_PRESENTATION, "")
+        };
+
+        // A sequence of emoji drawn as one takes up as much room as its first emoji, and one
+        // drawn part by part the room of all of them. Newer versions of unicode-width measure
+        // sequences as joined, so the parts are measured one by one rather than as a string.
+        if is_emoji_sequence(&text) {
+            if !self.joins_emoji_sequences {
+                return emoji_parts(&text)
+                    .iter()
+                    .map(|part| self.str_width(part))
+                    .fold(0, usize::saturating_add);
+            }
+            let head_width = self.str_width(&head(&text));
+            if head_width == 2 {
+                return head_width;
+            }
+        }
+
+        self.str_width(&text)
+    }
+
+    fn str_width(&self, text: &str) -> usize {
+        if self.ambiguous_width == 2 {
+            text.width_cjk()
+        } else {
+            text.width()
+        }
+    }
+}
+
+// The first character of a grapheme, along with the presentation selector following it
+fn head(grapheme: &str) -> String {
+    let mut chars = grapheme.chars();
+    let mut head: String = chars.next().into_iter().collect();
+    if let Some(selector) = chars
+        .next()
+        .filter(|c| matches!(*c, TEXT_PRESENTATION | EMOJI_PRESENTATION))
+    {
+        head.push(selector);
+    }
+    head
+}
+
+// The emoji of a sequence, each with the presentation selector following it, as a terminal
+// that doesn't join them draws them: the joiners are left out, and skin tones stand alone
+fn emoji_parts(grapheme: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut joined = true;
+    for c in grapheme.chars() {
+        match c {
+            ZERO_WIDTH_JOINER => joined = true,
+            TEXT_PRESENTATION | EMOJI_PRESENTATION => {
+                if let Some(part) = parts.last_mut() {
+                    part.push(c);
+                }
+            }
+            _ => {
+                if joined || matches!(c, '\u{1f3fb}'..='\u{1f3ff}') {
+                    parts.push(c.to_string());
+                } else if let Some(part) = parts.last_mut() {
+                    part.push(c);
+                }
+                joined = false;
+            }
+        }
+    }
+    parts
+}
+
+fn is_emoji_sequence(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .any(|c| c == ZERO_WIDTH_JOINER || matches!(c, '\u{1f3fb}'..='\u{1f3ff}'))
+}
+
+pub fn grapheme_width(grapheme: &str) -> usize {
+    WidthModel::current().grapheme_width(grapheme)
+}
+
+#[cfg(test)]
+pub fn text_width(text: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let model = WidthModel::current();
+    text.graphemes(true)
+        .map(|grapheme| model.grapheme_width(grapheme))
+        .fold(0, usize::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WidthModel;
+
+    #[test]
+    fn measures_emoji_and_ambiguous_characters() {
+        let model = WidthModel::default();
+        assert_eq!(model.grapheme_width("a"), 1);
+        assert_eq!(model.grapheme_width("界"), 2);
+        assert_eq!(model.grapheme_width("e\u{301}"), 1);
+        assert_eq!(model.grapheme_width("\u{263a}"), 1);
+        assert_eq!(model.grapheme_width("\u{263a}\u{fe0f}"), 2);
+        assert_eq!(model.grapheme_width("\u{2764}\u{fe0e}"), 1);
+        assert_eq!(model.grapheme_width("👩\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(model.grapheme_width("👋🏿"), 2);
+        assert_eq!(model.grapheme_width("❤\u{fe0f}\u{200d}🔥"), 2);
+        assert_eq!(model.grapheme_width("🇩🇪"), 2);
+        assert_eq!(model.grapheme_width("\u{a1}"), 1);
+        assert_eq!(model.grapheme_width("\u{1}"), 0);
+    }
+
+    #[test]
+    fn follows_the_terminal_settings() {
+        let model = WidthModel::calibrated(2, 4, 1);
+        assert_eq!(model.grapheme_width("\u{a1}"), 2);
+        assert_eq!(model.grapheme_width("a"), 1);
+        assert_eq!(model.grapheme_width("👩\u{200d}👧"), 4);
+        assert_eq!(model.grapheme_width("👋🏿"), 4);
+        assert_eq!(model.grapheme_width("❤\u{fe0f}\u{200d}🔥"), 3);
+        assert_eq!(model.grapheme_width("\u{263a}\u{fe0f}"), 1);
+    }
+}
@@ -0,0 +1,895 @@
+use super::arguments::Arguments;
+use super::terminal::{MemoryBackend, Size};
+use super::view::{position::Position, Indentation, WhitespaceGlyphs};
+use super::Editor;
+use crossterm::style::Color;
+use flate2::{read::GzDecoder, write::GzEncoder};
+use std::{
+    env, fs,
+    io::{Read, Write},
+    thread,
+    time::Duration,
+};
+
+mod keys;
+
+const SCREEN_SIZE: Size = Size {
+    width: 20,
+    height: 6,
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/editor/tests/fixtures");
+const SNAPSHOTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/editor/tests/snapshots");
+
+// Drives an editor rendering into memory with keystroke scripts (see `keys::parse`)
+struct Harness {
+    editor: Editor,
+    backend: MemoryBackend,
+}
+
+impl Harness {
+    fn new(fixture: Option<&str>) -> Self {
+        Self::with_arguments(&Arguments {
+            // Fixtures are opened by their path relative to the crate, so the status bar stays
+            // the same no matter where the crate is checked out
+            file_path: fixture.map(|name| format!("src/editor/tests/fixtures/{name}")),
+            // Tests share the fixtures, so edits to them must not leave swap files behind
+            no_swap: true,
+            ..Arguments::default()
+        })
+    }
+
+    fn with_arguments(arguments: &Arguments) -> Self {
+        let backend = MemoryBackend::new(SCREEN_SIZE);
+        let editor = Editor::with_backend(Box::new(backend.clone()), arguments).unwrap();
+        Self { editor, backend }
+    }
+
+    fn play(&mut self, script: &str) -> &mut Self {
+        for event in keys::parse(script) {
+            self.backend.push_event(event);
+        }
+        self.editor.run();
+        self
+    }
+
+    // Contents of the buffer shown in the active pane
+    fn text(&self) -> String {
+        self.editor.views[self.editor.active_view].text()
+    }
+
+    fn screen(&self) -> String {
+        let mut screen = String::new();
+        for row in 0..SCREEN_SIZE.height {
+            screen.push_str(&self.backend.row(row));
+            screen.push('\n');
+        }
+        screen
+    }
+
+    fn cursor(&self) -> Option<Position> {
+        self.backend.cursor()
+    }
+
+    // Compare the screen against `snapshots/<name>.txt`.
+    // Run with `UPDATE_SNAPSHOTS=1` to (re)write the snapshot instead.
+    fn assert_screen(&self, name: &str) {
+        let path = format!("{SNAPSHOTS}/{name}.txt");
+        let screen = self.screen();
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, &screen).unwrap();
+            return;
+        }
+
+        let Ok(expected) = fs::read_to_string(&path) else {
+            panic!("Missing snapshot {path}, run with UPDATE_SNAPSHOTS=1 to create it:\n{screen}");
+        };
+        assert_eq!(screen, expected, "Screen does not match snapshot {name}");
+    }
+}
+
+fn fixture_text(name: &str) -> String {
+    fs::read_to_string(format!("{FIXTURES}/{name}")).unwrap()
+}
+
+// A scratch file for tests that save, unique to the test and this run
+fn temp_file(name: &str, contents: &[u8]) -> String {
+    let path = env::temp_dir().join(format!("script-pad-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn with_file(path: &str) -> Harness {
+    Harness::with_arguments(&Arguments {
+        file_path: Some(path.to_string()),
+        no_swap: true,
+        ..Arguments::default()
+    })
+}
+
+#[test]
+fn renders_welcome_screen_without_a_file() {
+    let mut harness = Harness::new(None);
+    harness.play("");
+
+    harness.assert_screen("welcome");
+    assert!(
+        harness
+            .backend
+            .cell(&Position { row: 4, col: 0 })
+            .unwrap()
+            .style
+            .reverse
+    );
+}
+
+#[test]
+fn typing_on_the_welcome_screen_fills_the_buffer() {
+    let mut harness = Harness::new(None);
+    harness.play("abc<Left><BS>d<Enter>");
+
+    assert_eq!(harness.text(), "ad\nc");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 0 }));
+    harness.assert_screen("typed_on_welcome");
+}
+
+#[test]
+fn backspace_at_line_start_joins_with_previous_line() {
+    let mut harness = Harness::new(Some("lines.txt"));
+    harness.play("<Down><Down><BS>");
+
+    assert_eq!(harness.text(), "first line\nsecond linethird\n");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 11 }));
+    harness.assert_screen("joined_lines");
+}
+
+#[test]
+fn delete_at_line_end_joins_with_next_line() {
+    let mut harness = Harness::new(Some("lines.txt"));
+    harness.play("<End><Del>");
+
+    assert_eq!(harness.text(), "first linesecond line\nthird\n");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 10 }));
+}
+
+#[test]
+fn home_and_end_move_within_the_line() {
+    let mut harness = Harness::new(Some("lines.txt"));
+    harness.play("<Down><End>!<Home>>");
+
+    assert_eq!(harness.text(), "first line\n>second line!\nthird\n");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 1 }));
+}
+
+#[test]
+fn wide_graphemes_take_two_columns() {
+    let mut harness = Harness::new(Some("wide.txt"));
+    harness.play("<Right><Right>x<Down>");
+
+    assert_eq!(harness.text(), "ＡＢxＣ界 wide\nplain\n");
+    // Moving down keeps the screen column, not the grapheme index
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 5 }));
+    harness.assert_screen("wide_graphemes");
+}
+
+#[test]
+fn zero_width_graphemes_are_replaced_on_screen_only() {
+    let mut harness = Harness::new(Some("zero_width.txt"));
+    harness.play("<Right><Right>x<Down><Home><Del>");
+
+    assert_eq!(harness.text(), "a\u{200b}xb\u{200b}c\nx\n");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 0 }));
+    harness.assert_screen("zero_width");
+}
+
+#[test]
+fn long_lines_scroll_horizontally() {
+    let mut harness = Harness::new(Some("long.txt"));
+    // The wide grapheme cut off by the right edge is shown as an ellipsis
+    harness.play("");
+    harness.assert_screen("long_line_start");
+
+    // As is the one cut off by the left edge once scrolled
+    harness.play("<End>");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 18 }));
+    harness.assert_screen("long_line_end");
+
+    harness.play("<Home>");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 0 }));
+    harness.assert_screen("long_line_start");
+
+    assert_eq!(harness.text(), fixture_text("long.txt"));
+}
+
+#[test]
+fn typed_combining_characters_join_the_previous_grapheme() {
+    let mut harness = Harness::new(None);
+    harness.play("e\u{301}x👩\u{200d}👧<Left>");
+
+    assert_eq!(harness.text(), "e\u{301}x👩\u{200d}👧");
+    // "é" and "x" take one column each, the family emoji two
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 2 }));
+    assert_eq!(harness.backend.row(0), "e\u{301}x👩\u{200d}👧");
+}
+
+#[test]
+fn chars_typed_through_altgr_are_inserted() {
+    // Terminals report AltGr as Ctrl+Alt
+    let mut harness = Harness::new(None);
+    harness.play("<C-A-@><C-A-€>");
+    assert_eq!(harness.text(), "@€");
+}
+
+#[test]
+fn base_typed_before_a_lone_combining_mark_takes_it_over() {
+    let mut harness = Harness::new(Some("zero_width.txt"));
+    harness.play("<Down>e");
+
+    assert_eq!(harness.text(), "a\u{200b}b\u{200b}c\ne\u{301}x\n");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 1 }));
+}
+
+#[test]
+fn backspace_removes_whole_clusters_or_single_code_points() {
+    let mut harness = Harness::new(None);
+    harness.play("ae\u{301}<BS>");
+    assert_eq!(harness.text(), "a");
+
+    harness.play("e\u{301}<A-BS>");
+    assert_eq!(harness.text(), "ae");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 2 }));
+
+    harness.play("<A-BS><A-BS>");
+    assert_eq!(harness.text(), "");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 0 }));
+}
+
+#[test]
+fn ambiguous_width_setting_widens_the_caret_steps() {
+    let mut harness = Harness::with_arguments(&Arguments {
+        ambiguous_width: Some(2),
+        ..Arguments::default()
+    });
+    harness.play("\u{a1}a");
+
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 3 }));
+    assert_eq!(
+        harness
+            .backend
+            .cell(&Position { row: 0, col: 2 })
+            .unwrap()
+            .symbol,
+        "a"
+    );
+}
+
+#[test]
+fn parses_command_line_options() {
+    let parse = |args: &[&str]| Arguments::parse(args.iter().map(ToString::to_string));
+
+    let arguments = parse(&["--ambiguous-width=2", "--probe-widths", "notes.txt"]).unwrap();
+    assert_eq!(arguments.ambiguous_width, Some(2));
+    assert!(arguments.probe_widths);
+    assert_eq!(arguments.file_path.as_deref(), Some("notes.txt"));
+
+    assert!(parse(&["--ambiguous-width=3"]).is_err());
+    assert!(parse(&["--unknown"]).is_err());
+}
+
+#[test]
+fn control_and_invisible_characters_are_spelled_out() {
+    let mut harness = Harness::new(Some("controls.txt"));
+    harness.play("");
+
+    harness.assert_screen("controls");
+    let style_at = |col| {
+        harness
+            .backend
+            .cell(&Position { row: 1, col })
+            .unwrap()
+            .style
+    };
+    // ZWSP, then the RLO right after the BOM
+    assert_eq!(style_at(0).foreground, Some(Color::Magenta));
+    assert_eq!(style_at(12).foreground, Some(Color::Red));
+    assert_ne!(style_at(0), style_at(12));
+    assert_eq!(
+        harness
+            .backend
+            .cell(&Position { row: 0, col: 1 })
+            .unwrap()
+            .style
+            .foreground,
+        Some(Color::Cyan)
+    );
+
+    // The caret steps over each notation as a whole
+    harness.play("<Right><Right>");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 3 }));
+}
+
+#[test]
+fn inspecting_shows_the_code_points_under_the_caret() {
+    let mut harness = Harness::new(Some("controls.txt"));
+    harness.play("<Right><A-i>");
+    assert_eq!(harness.backend.row(5), "U+0000 | UTF-8 00 |");
+
+    let mut harness = Harness::new(None);
+    harness.play("e\u{301}<Left><A-i>");
+    assert!(harness.backend.row(5).starts_with("U+0065 U+0301 | UTF"));
+}
+
+#[test]
+fn whitespace_can_be_shown_and_hidden() {
+    let mut harness = Harness::new(Some("whitespace.txt"));
+    harness.play("");
+    assert_eq!(harness.backend.row(0), "a b c\u{a0}d");
+
+    harness.play("<A-w>");
+    harness.assert_screen("whitespace_shown");
+    let style_at = |col| {
+        harness
+            .backend
+            .cell(&Position { row: 0, col })
+            .unwrap()
+            .style
+    };
+    assert!(style_at(1).dim);
+    assert_eq!(style_at(1).background, None);
+    // The trailing space and tab are highlighted, the line end marker is not
+    assert_eq!(style_at(7).background, Some(Color::Red));
+    assert_eq!(style_at(8).background, Some(Color::Red));
+    assert_eq!(style_at(9).background, None);
+    assert_eq!(harness.text(), fixture_text("whitespace.txt"));
+
+    harness.play("<A-w>");
+    assert_eq!(harness.backend.row(0), "a b c\u{a0}d");
+}
+
+#[test]
+fn whitespace_glyphs_are_configurable() {
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some("src/editor/tests/fixtures/whitespace.txt".to_string()),
+        show_whitespace: true,
+        whitespace_glyphs: WhitespaceGlyphs::parse("space=_,eol=$").unwrap(),
+        ..Arguments::default()
+    });
+    harness.play("");
+
+    assert_eq!(harness.backend.row(0), "a_b→c⍽d_→$");
+    assert_eq!(harness.backend.row(1), "$");
+    assert!(WhitespaceGlyphs::parse("space=ab").is_err());
+    assert!(WhitespaceGlyphs::parse("newline=$").is_err());
+}
+
+#[test]
+fn line_endings_bom_and_missing_final_newline_survive_a_save() {
+    let path = temp_file("crlf.txt", "\u{feff}one\r\ntwo".as_bytes());
+    let mut harness = with_file(&path);
+    harness.play("<Down><End>!<C-s>");
+
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status.file_format_string(), "CRLF BOM noeol");
+    // The BOM is not part of the text, and the CRLF is not shown
+    assert_eq!(harness.backend.row(0), "one");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 4 }));
+    assert_eq!(fs::read(&path).unwrap(), "\u{feff}one\r\ntwo!".as_bytes());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn line_endings_and_final_newline_can_be_converted() {
+    let path = temp_file("convert.txt", b"one\r\ntwo\r\n");
+    let mut harness = with_file(&path);
+    harness.play("<A-l>");
+    assert_eq!(harness.text(), "one\ntwo\n");
+    assert_eq!(harness.backend.row(5), "Line endings convert");
+
+    harness.play("<A-n>");
+    assert_eq!(harness.text(), "one\ntwo");
+    harness.play("<A-n><A-l><C-s>");
+    assert_eq!(fs::read(&path).unwrap(), b"one\r\ntwo\r\n");
+
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status.file_format_string(), "CRLF");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn legacy_encodings_are_detected_and_written_back() {
+    let path = temp_file("latin.txt", b"caf\xe9 cr\xe8me br\xfbl\xe9e\n");
+    let mut harness = with_file(&path);
+    harness.play("<End>!<C-s>");
+
+    assert_eq!(harness.text(), "café crème brûlée!\n");
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status.file_format_string(), "windows-1252 LF");
+    assert_eq!(
+        fs::read(&path).unwrap(),
+        b"caf\xe9 cr\xe8me br\xfbl\xe9e!\n"
+    );
+
+    // A character the encoding can't hold stops the save, leaving the file as it was
+    harness.play("日<C-s>");
+    assert_eq!(harness.backend.row(5), "Error writing file:");
+    assert_eq!(
+        fs::read(&path).unwrap(),
+        b"caf\xe9 cr\xe8me br\xfbl\xe9e!\n"
+    );
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn files_can_be_reopened_with_another_encoding() {
+    let path = temp_file("sjis.txt", b"\x93\xfa\x96\x7b\n");
+    let mut harness = with_file(&path);
+    harness.play("<A-e>latin1<Enter>");
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status.file_format_string(), "windows-1252 LF");
+
+    harness.play("<A-e>nonsense<Enter>");
+    assert_eq!(harness.backend.row(5), "Unknown encoding non");
+
+    harness.play("<A-e>shift_jis<Enter>");
+    assert_eq!(harness.text(), "日本\n");
+    assert_eq!(harness.backend.row(5), "Reopened as Shift_JI");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn binary_files_are_edited_in_the_hex_view() {
+    let path = temp_file("binary.bin", b"\x7fELF\0\x01\x02\xffHi\0\0\x10");
+    let mut harness = with_file(&path);
+    harness.play("");
+    assert_eq!(harness.backend.row(0), "0000  7F 45 4C  .EL");
+    assert_eq!(harness.backend.row(3), "0009  69 00 00  i..");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 6 }));
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status.file_format_string(), "binary");
+
+    // Hex digits overwrite the byte under the caret one nibble at a time, anything else is ignored
+    harness.play("<Down>azb<C-s>");
+    assert_eq!(harness.cursor(), Some(Position { row: 1, col: 9 }));
+    assert_eq!(
+        fs::read(&path).unwrap(),
+        b"\x7fEL\xab\0\x01\x02\xffHi\0\0\x10"
+    );
+
+    harness.play("<C-f>\"Hi\"<Enter>");
+    assert_eq!(harness.backend.row(5), "Found at offset 8 (0");
+    assert_eq!(harness.cursor(), Some(Position { row: 2, col: 12 }));
+    harness.play("<C-f>0000<Enter>");
+    assert_eq!(harness.cursor(), Some(Position { row: 3, col: 9 }));
+    harness.play("<C-f>0g<Enter>");
+    assert_eq!(harness.backend.row(5), "Use hex digits (e.g.");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn saving_can_keep_a_backup_of_the_previous_contents() {
+    let path = temp_file("backup.txt", b"old\n");
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some(path.clone()),
+        ..Arguments::parse(["--backup".to_string()].into_iter()).unwrap()
+    });
+    harness.play("new <C-s>");
+
+    assert_eq!(harness.backend.row(5), "File saved successfu");
+    assert_eq!(fs::read(&path).unwrap(), b"new old\n");
+    let backup = format!("{path}~");
+    assert_eq!(fs::read(&backup).unwrap(), b"old\n");
+    fs::remove_file(path).unwrap();
+    fs::remove_file(backup).unwrap();
+}
+
+#[test]
+fn unsaved_changes_can_be_recovered_from_the_swap_file() {
+    let path = temp_file("recover.txt", b"one\ntwo\n");
+    let swap_path = env::temp_dir().join(format!("script-pad-{}-recover.txt", std::process::id()));
+    let swap_path = swap_path.with_file_name(format!(
+        ".{}.swp",
+        swap_path.file_name().unwrap().to_string_lossy()
+    ));
+    let open = || {
+        Harness::with_arguments(&Arguments {
+            file_path: Some(path.clone()),
+            ..Arguments::default()
+        })
+    };
+
+    // The changes are journaled once typing pauses, then the editor goes away without saving
+    {
+        let mut harness = open();
+        harness.play("X");
+        assert!(swap_path.exists());
+    }
+
+    let mut harness = open();
+    harness.play("");
+    assert_eq!(harness.backend.row(5), "Swap file found. Rec");
+    harness.play("d<Enter>");
+    // The diff opens in a pane below
+    assert_eq!(harness.backend.row(2), "@@ line 1 @@");
+    assert_eq!(harness.backend.row(3), "- one");
+    assert_eq!(harness.text(), "one\ntwo\n");
+
+    harness.play("r<Enter>");
+    assert_eq!(harness.text(), "Xone\ntwo\n");
+    harness.play("<C-s>");
+    assert_eq!(fs::read(&path).unwrap(), b"Xone\ntwo\n");
+    assert!(!swap_path.exists());
+    drop(harness);
+
+    {
+        let mut harness = open();
+        harness.play("Y");
+    }
+    let mut harness = open();
+    harness.play("x<Enter>");
+    assert_eq!(harness.text(), "Xone\ntwo\n");
+    assert!(!swap_path.exists());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn changes_by_other_programs_are_noticed() {
+    let path = temp_file("external.txt", b"mine\n");
+    let mut harness = with_file(&path);
+    harness.play("X");
+
+    // Noticed once the editor is idle, and offered for reloading
+    fs::write(&path, b"theirs, longer\n").unwrap();
+    harness.play("");
+    assert_eq!(harness.backend.row(5), "File changed on disk");
+    harness.play("d<Enter>");
+    assert_eq!(harness.backend.row(2), "@@ line 1 @@");
+    assert_eq!(harness.backend.row(3), "- theirs, longer");
+    harness.play("r<Enter>");
+    assert_eq!(harness.text(), "theirs, longer\n");
+
+    // Keeping your version lets saving replace theirs; the caret stayed where it was
+    harness.play("Y");
+    fs::write(&path, b"again\n").unwrap();
+    harness.play("<C-s>");
+    assert_eq!(fs::read(&path).unwrap(), b"again\n");
+    harness.play("k<Enter>");
+    assert_eq!(fs::read(&path).unwrap(), b"tYheirs, longer\n");
+    assert_eq!(harness.backend.row(5), "File saved successfu");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_files_open_as_new_buffers() {
+    let path = env::temp_dir()
+        .join(format!("script-pad-{}-new.txt", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    let _ = fs::remove_file(&path);
+    let mut harness = with_file(&path);
+    harness.play("");
+    assert_eq!(harness.backend.row(5), "New file /tmp/script");
+    // An empty new file shows as such rather than as the welcome screen
+    assert_eq!(harness.backend.row(0), "");
+
+    harness.play("hi<C-s>");
+    assert_eq!(fs::read(&path).unwrap(), b"hi");
+    assert_eq!(harness.backend.row(5), "Saved as /tmp/script");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn scratch_buffers_are_saved_under_a_new_name() {
+    let directory = env::temp_dir().join(format!("script-pad-{}-save-as", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir(&directory).unwrap();
+    let directory = directory.to_string_lossy().into_owned();
+    fs::write(format!("{directory}/existing.txt"), b"kept").unwrap();
+
+    let mut harness = Harness::new(None);
+    // Typing on the welcome screen starts a scratch buffer, which stays after deleting all of it
+    harness.play("x<BS>");
+    assert_eq!(harness.backend.row(0), "");
+    harness.play("scratch<C-s>");
+    assert_eq!(harness.backend.row(5), "Save as:");
+
+    // Tab completes names in the directory typed so far
+    harness.play(&format!("{directory}/ex<Tab>"));
+    assert_eq!(
+        harness.editor.command_bar.value(),
+        format!("{directory}/existing.txt")
+    );
+    harness.play("<Enter>");
+    assert_eq!(harness.backend.row(5), "File exists. Overwri");
+    harness.play("n<Enter>");
+    assert_eq!(harness.backend.row(5), "Not saved.");
+    assert_eq!(
+        fs::read(format!("{directory}/existing.txt")).unwrap(),
+        b"kept"
+    );
+
+    harness.play("<A-a>");
+    assert_eq!(harness.editor.command_bar.value(), "");
+    harness.play(&format!("{directory}/ex<Tab><Enter>y<Enter>"));
+    assert_eq!(
+        fs::read(format!("{directory}/existing.txt")).unwrap(),
+        b"scratch"
+    );
+
+    // From then on the buffer is about that file, and Save-As starts from its name
+    harness.play("<A-a><BS><BS><BS>md<Enter>");
+    assert_eq!(
+        fs::read(format!("{directory}/existing.md")).unwrap(),
+        b"scratch"
+    );
+    assert_eq!(
+        harness.editor.views[harness.editor.active_view].file_path(),
+        Some(format!("{directory}/existing.md"))
+    );
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn read_only_buffers_refuse_edits_until_toggled() {
+    let path = temp_file("readonly.txt", b"view me\n");
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some(path.clone()),
+        no_swap: true,
+        readonly: true,
+        ..Arguments::default()
+    });
+    harness.play("x<Del>");
+    assert_eq!(harness.text(), "view me\n");
+    assert_eq!(harness.backend.row(5), "Buffer is read-only,");
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert!(status.is_read_only);
+    assert_eq!(status.read_only_indicator_string(), "[RO]");
+    harness.play("<A-l><C-s>");
+    assert_eq!(harness.backend.row(5), "Buffer is read-only,");
+    assert_eq!(fs::read(&path).unwrap(), b"view me\n");
+
+    harness.play("<A-r>x<C-s>");
+    assert_eq!(fs::read(&path).unwrap(), b"xview me\n");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn followed_files_show_what_is_appended() {
+    let path = temp_file("follow.log", b"1\n2\n3\n4\n5\n6\n");
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some(path.clone()),
+        no_swap: true,
+        follow: true,
+        ..Arguments::default()
+    });
+    harness.play("x");
+    assert_eq!(harness.backend.row(5), "Buffer is read-only,");
+    let status = |harness: &Harness| harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status(&harness).current_line, 6);
+    assert_eq!(status(&harness).follow_indicator_string(), "[follow]");
+
+    // Appended lines are read when idle, and the view keeps up with them
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"7\n8\n").unwrap();
+    harness.play("");
+    assert_eq!(harness.text(), "1\n2\n3\n4\n5\n6\n7\n8\n");
+    assert_eq!(status(&harness).current_line, 8);
+    assert_eq!(harness.backend.row(1), "7");
+    assert_eq!(harness.backend.row(2), "8");
+
+    // Until the caret is moved off the last line
+    harness.play("<Up>");
+    file.write_all(b"9\n").unwrap();
+    harness.play("");
+    assert_eq!(status(&harness).current_line, 7);
+    assert_eq!(status(&harness).follow_indicator_string(), "[paused]");
+
+    // A file replaced by one that can't be followed leaves the buffer as it was
+    fs::write(&path, b"\0\x01\x02").unwrap();
+    harness.play("");
+    assert_eq!(harness.backend.row(5), "Could not read the f");
+    assert_eq!(harness.text(), "1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+    assert_eq!(status(&harness).follow_indicator_string(), "[paused]");
+
+    // A truncated (e.g. rotated) file is read anew
+    fs::write(&path, b"fresh\n").unwrap();
+    harness.play("");
+    assert_eq!(harness.text(), "fresh\n");
+
+    harness.play("<A-f><PgUp><Home>x");
+    assert_eq!(status(&harness).following, None);
+    assert_eq!(harness.text(), "xfresh\n");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn compressed_files_are_saved_compressed_again() {
+    let mut compressed = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    compressed.write_all(b"one\ntwo\n").unwrap();
+    let path = temp_file("rotated.log.gz", &compressed.finish().unwrap());
+    let mut harness = with_file(&path);
+    harness.play("x<C-s>");
+    let status = harness.editor.views[harness.editor.active_view].get_status();
+    assert_eq!(status.file_format_string(), "gz LF");
+
+    let mut saved = String::new();
+    GzDecoder::new(fs::File::open(&path).unwrap())
+        .read_to_string(&mut saved)
+        .unwrap();
+    assert_eq!(saved, "xone\ntwo\n");
+    fs::remove_file(path).unwrap();
+
+    // A new file is compressed when its name says so
+    let path = env::temp_dir()
+        .join(format!("script-pad-{}-new.txt.xz", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    let _ = fs::remove_file(&path);
+    with_file(&path).play("hi<C-s>");
+    assert!(fs::read(&path).unwrap().starts_with(b"\xfd7zXZ\0"));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn new_lines_are_indented_like_the_code_around_them() {
+    let path = temp_file("indent.rs", b"");
+    let mut harness = with_file(&path);
+    // Closers come with their openers, so the ones of the blocks are there already
+    harness.play("fn main() {<Enter>let x = [<Enter>1,<Down><End>;");
+    assert_eq!(
+        harness.text(),
+        "fn main() {\n    let x = [\n        1,\n    ];\n}"
+    );
+
+    // Between a pair the closer goes on its own line, and the caret on the one in between
+    harness.play("<Down><End><Enter>if x {}<Left><Enter>y");
+    assert_eq!(
+        harness.text().lines().skip(5).collect::<Vec<_>>(),
+        ["if x {", "    y", "}"]
+    );
+
+    // Lines are indented and outdented by a level, keeping the caret on the same text
+    harness.play("<A-.>z<A-,><A-,><A-,>");
+    assert_eq!(harness.text().lines().nth(6), Some("yz"));
+    fs::remove_file(path).unwrap();
+
+    // A closer typed first thing on a line takes it back to the level of its opener
+    let path = temp_file("indent-closer.rs", b"fn f() {\n    y");
+    let mut harness = with_file(&path);
+    harness.play("<Down><End><Enter>}");
+    assert_eq!(harness.text(), "fn f() {\n    y\n}");
+    fs::remove_file(path).unwrap();
+
+    // Other files only keep the indentation, which is a tab if told so
+    let path = temp_file("indent.txt", b"\tnote {");
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some(path.clone()),
+        no_swap: true,
+        indent: Some(Indentation::Tab),
+        ..Arguments::default()
+    });
+    harness.play("<End><Enter>x<A-.>");
+    assert_eq!(harness.text(), "\tnote {\n\t\tx");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn brackets_and_quotes_are_typed_in_pairs() {
+    let mut harness = Harness::new(None);
+    harness.play("say(\"hi\")");
+    assert_eq!(harness.text(), "say(\"hi\")");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 9 }));
+
+    // An empty pair goes with one Backspace, and apostrophes stay on their own
+    harness.play(" [<BS> don't");
+    assert_eq!(harness.text(), "say(\"hi\")  don't");
+    harness.play("<Home>{");
+    assert_eq!(harness.text(), "{say(\"hi\")  don't");
+
+    // A closer the line still needs is typed, rather than moving over the next one
+    let mut harness = Harness::new(None);
+    harness.play("f(g(x))<BS><Left>)");
+    assert_eq!(harness.text(), "f(g(x))");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 6 }));
+}
+
+#[test]
+fn edits_are_undone_word_by_word() {
+    let path = temp_file("undo.txt", b"first\n");
+    let mut harness = with_file(&path);
+    harness.play("<End>, second<Enter>x");
+    assert_eq!(harness.text(), "first, second\nx\n");
+
+    harness.play("<C-z><C-z>");
+    assert_eq!(harness.text(), "first, second\n");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 13 }));
+    harness.play("<C-z><C-z>");
+    assert_eq!(harness.text(), "first,\n");
+    harness.play("<C-z>");
+    assert_eq!(harness.text(), "first\n");
+    assert!(!harness.editor.views[harness.editor.active_view].is_modified());
+    harness.play("<C-z>");
+    assert_eq!(harness.backend.row(5), "Nothing to undo.");
+
+    // Redoing goes forward again, until a new edit drops what was left to redo
+    harness.play("<C-y><C-y>");
+    assert_eq!(harness.text(), "first, \n");
+    assert_eq!(harness.cursor(), Some(Position { row: 0, col: 7 }));
+    harness.play("2<C-y>");
+    assert_eq!(harness.text(), "first, 2\n");
+    assert_eq!(harness.backend.row(5), "Nothing to redo.");
+}
+
+#[test]
+fn the_first_buffer_goes_to_stdout_on_quitting() {
+    let path = temp_file("stdout.txt", b"caf\xe9\n");
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some(path.clone()),
+        no_swap: true,
+        stdout: true,
+        ..Arguments::default()
+    });
+    // Written back in the encoding it was read in, like saving
+    harness.play("X");
+    assert_eq!(harness.editor.output().unwrap(), b"Xcaf\xe9\n");
+
+    // Characters the encoding can't hold keep the editor open
+    harness.play("\u{65e5}<C-q>");
+    assert!(!harness.editor.should_exit);
+    assert_eq!(harness.backend.row(5), "Could not write to s");
+
+    // Changes going to stdout don't count as unsaved
+    harness.play("<BS><C-q>");
+    assert!(harness.editor.should_exit);
+    // Keep the bytes out of the test's own output
+    harness.editor.should_exit = false;
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn large_files_are_only_read_in_once_edited() {
+    let path = temp_file("large.log", b"first\r\nsecond\r\nthird");
+    let mut harness = Harness::with_arguments(&Arguments {
+        file_path: Some(path.clone()),
+        no_swap: true,
+        large_file: Some(0),
+        ..Arguments::default()
+    });
+    let view = harness.editor.active_view;
+    while harness.editor.views[view]
+        .get_status()
+        .indexing_progress
+        .is_some()
+    {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    harness.play("<Down>");
+    assert_eq!(harness.backend.row(0), "first");
+    assert_eq!(harness.backend.row(2), "third");
+    let status = harness.editor.views[view].get_status();
+    assert_eq!((status.current_line, status.total_lines), (1, 3));
+    assert_eq!(status.file_format_string(), "CRLF noeol");
+    assert!(!status.is_read_only);
+    assert!(harness.editor.views[view].is_large_file());
+
+    // Editing it takes reading all of it, which is asked first and done in the background
+    harness.play("x");
+    assert_eq!(harness.backend.row(1), "second");
+    assert_eq!(harness.backend.row(5), "Read the whole file");
+    harness.play("n<Enter>");
+    assert!(harness.editor.views[view].is_large_file());
+    assert_eq!(harness.backend.row(5), "Not read, a higher -");
+    harness.play("xy<Enter>");
+    while harness.editor.views[view].is_large_file() {
+        thread::sleep(Duration::from_millis(1));
+        harness.play("");
+    }
+    assert_eq!(harness.backend.row(5), "The whole file was r");
+
+    // After which it is edited and saved like any other
+    harness.play("x");
+    assert_eq!(harness.backend.row(1), "xsecond");
+    harness.play("<C-s>");
+    assert_eq!(fs::read(&path).unwrap(), b"first\r\nxsecond\r\nthird");
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,84 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+// Turn a keystroke script into the key events a terminal would send.
+// Plain characters are typed as-is, while special keys are written in angle brackets,
+// optionally with modifiers: `"abc<Enter><Left><BS><C-s><A-Left><lt>"`.
+pub fn parse(script: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut chars = script.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            events.push(key(KeyCode::Char(c), KeyModifiers::NONE));
+            continue;
+        }
+
+        let name: String = chars.by_ref().take_while(|c| *c != '>').collect();
+        events.push(parse_key(&name));
+    }
+
+    events
+}
+
+fn parse_key(name: &str) -> Event {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    loop {
+        let modifier = match rest.get(..2) {
+            Some("C-") => KeyModifiers::CONTROL,
+            Some("A-") => KeyModifiers::ALT,
+            Some("S-") => KeyModifiers::SHIFT,
+            _ => break,
+        };
+        modifiers |= modifier;
+        rest = &rest[2..];
+    }
+
+    let code = match rest {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "BS" => KeyCode::Backspace,
+        "Del" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PgUp" => KeyCode::PageUp,
+        "PgDn" => KeyCode::PageDown,
+        "lt" => KeyCode::Char('<'),
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => panic!("Unknown key in script: <{name}>"),
+            }
+        }
+    };
+
+    key(code, modifiers)
+}
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+    Event::Key(KeyEvent::new(code, modifiers))
+}
+
+#[test]
+fn parses_plain_and_special_keys() {
+    assert_eq!(
+        parse("ab<Enter><Left><BS><lt><C-s><A-Left><Del>"),
+        vec![
+            key(KeyCode::Char('a'), KeyModifiers::NONE),
+            key(KeyCode::Char('b'), KeyModifiers::NONE),
+            key(KeyCode::Enter, KeyModifiers::NONE),
+            key(KeyCode::Left, KeyModifiers::NONE),
+            key(KeyCode::Backspace, KeyModifiers::NONE),
+            key(KeyCode::Char('<'), KeyModifiers::NONE),
+            key(KeyCode::Char('s'), KeyModifiers::CONTROL),
+            key(KeyCode::Left, KeyModifiers::ALT),
+            key(KeyCode::Delete, KeyModifiers::NONE),
+        ]
+    );
+}
@@ -1,42 +1,322 @@
 use super::command::{Command, Direction};
-use super::terminal::{Size, Terminal};
-use buffer::Buffer;
+use super::documentstatus::DocumentStatus;
+use super::terminal::{grapheme_width, Rect, Style, Terminal};
+use buffer::{Buffer, Compression, LineEnding};
 use caret::Caret;
+use crossterm::style::Color;
+use encoding_rs::Encoding;
+use hex::{HexCaret, HexLayout};
+use indentation::IndentRules;
+use line::{GraphemeKind, Span};
+use pairs::Typed;
 use position::Position;
+use std::{
+    cell::RefCell,
+    io::{Error, ErrorKind},
+    rc::Rc,
+};
 
 mod buffer;
+
+pub use buffer::set_large_file_threshold;
 mod caret;
+mod hex;
+mod indentation;
 mod line;
+mod pairs;
 pub mod position;
+mod whitespace;
+
+pub use indentation::Indentation;
+pub use whitespace::WhitespaceGlyphs;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const WHITESPACE_STYLE: Style = Style {
+    reverse: false,
+    dim: true,
+    foreground: None,
+    background: None,
+};
+
+// A window onto a buffer. Several views may share the same buffer (e.g. split panes),
+// each keeping its own caret and scroll offset.
 pub struct View {
     caret: Caret,
+    // Caret used instead while a binary file is shown in the hex view
+    hex_caret: HexCaret,
     scroll_offset: Position,
-    buffer: Buffer,
+    buffer: Rc<RefCell<Buffer>>,
+    seen_revision: usize,
+    // Lines the buffer had at that revision, to tell whether the caret was on the last one
+    seen_height: usize,
     needs_redraw: bool,
-    terminal_size: Size,
+    area: Rect,
+    // Glyphs to show whitespace with, if it is shown at all
+    whitespace: Option<WhitespaceGlyphs>,
 }
 
 impl Default for View {
     fn default() -> Self {
         Self {
             caret: Caret::default(),
+            hex_caret: HexCaret::default(),
             scroll_offset: Position::default(),
-            buffer: Buffer::default(),
+            buffer: Rc::new(RefCell::new(Buffer::default())),
+            seen_revision: 0,
+            seen_height: 0,
             needs_redraw: true,
-            terminal_size: Terminal::size().unwrap_or_default(),
+            area: Rect::default(),
+            whitespace: None,
         }
     }
 }
 
 impl View {
-    pub fn load(&mut self, file_path: &str) {
-        if let Ok(buffer) = Buffer::load(file_path) {
-            self.buffer = buffer;
-            self.needs_redraw = true;
+    // Load a file, or start a new one if there is no file at the path yet
+    pub fn load(&mut self, file_path: &str) -> Result<(), Error> {
+        let buffer = match Buffer::load(file_path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Buffer::new_file(file_path),
+            result => result?,
+        };
+        self.buffer = Rc::new(RefCell::new(buffer));
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    pub fn load_stdin(&mut self) -> Result<(), Error> {
+        self.buffer = Rc::new(RefCell::new(Buffer::read_stdin()?));
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Read the file again in the given encoding. The buffer is replaced in place, so other
+    // views onto it show the new text as well.
+    pub fn reload_with_encoding(&mut self, encoding: &'static Encoding) -> Result<(), Error> {
+        self.reload_from_disk(Some(encoding))
+    }
+
+    // Read the file again as it is on disk now, dropping any unsaved changes
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.buffer.borrow_mut().discard_swap()?;
+        self.reload_from_disk(None)
+    }
+
+    fn reload_from_disk(&mut self, encoding: Option<&'static Encoding>) -> Result<(), Error> {
+        let Some(file_path) = self.file_path() else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+        let mut buffer = self.buffer.borrow_mut();
+        // Following is taken up again afterwards, with the buffer as it was before
+        let following = buffer.is_following();
+        buffer.stop_following();
+        let (revision, read_only) = (buffer.revision, buffer.read_only);
+        *buffer = Buffer::load_with_encoding(&file_path, encoding)?;
+        buffer.revision = revision.wrapping_add(1);
+        // Whether it may be edited was decided already, by the file or the user
+        buffer.read_only = read_only;
+        if following {
+            buffer.start_following()?;
+        }
+        Ok(())
+    }
+
+    // Follow the file as other programs append to it, or stop doing so. Following starts at the
+    // end, like `tail -f` does. Returns whether the file is followed now.
+    pub fn toggle_following(&mut self) -> Result<bool, Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_following() {
+            buffer.stop_following();
+            return Ok(false);
+        }
+        buffer.start_following()?;
+        self.caret.move_caret(&Direction::PageDown, &mut buffer);
+        self.caret.clamp(&mut buffer);
+        self.seen_height = buffer.height();
+        drop(buffer);
+        self.adjust_screen_to_offset();
+        self.needs_redraw = true;
+        Ok(true)
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.buffer.borrow().is_following()
+    }
+
+    // Load what was appended to a followed file, see `Buffer::read_appended`
+    pub fn read_appended(&mut self) -> Result<bool, Error> {
+        self.buffer.borrow_mut().read_appended()
+    }
+
+    // Whether another program changed the file since it was read or saved
+    pub fn changed_on_disk(&mut self) -> bool {
+        self.buffer.borrow_mut().changed_on_disk()
+    }
+
+    pub fn keep_over_disk(&mut self) {
+        self.buffer.borrow_mut().keep_over_disk();
+    }
+
+    pub fn disk_diff(&self) -> Result<String, Error> {
+        self.buffer.borrow().disk_diff()
+    }
+
+    // Warning about bytes in the file that were not valid in the encoding it was read in
+    pub fn decode_warning(&self) -> Option<String> {
+        let buffer = self.buffer.borrow();
+        buffer.had_decode_errors.then(|| {
+            format!(
+                "Some bytes are not valid {} and were replaced. Alt-E reopens with another encoding.",
+                buffer.encoding.name()
+            )
+        })
+    }
+
+    // A view onto text that isn't backed by a file
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            buffer: Rc::new(RefCell::new(Buffer::from_text(text))),
+            ..Self::default()
+        }
+    }
+
+    // Create another view onto the same buffer, starting at the same caret location
+    pub fn split(&self) -> Self {
+        Self {
+            caret: self.caret,
+            hex_caret: self.hex_caret,
+            scroll_offset: self.scroll_offset,
+            buffer: Rc::clone(&self.buffer),
+            seen_revision: self.seen_revision,
+            seen_height: self.seen_height,
+            needs_redraw: true,
+            area: self.area,
+            whitespace: self.whitespace,
+        }
+    }
+
+    pub fn shares_buffer_with(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.buffer, &other.buffer)
+    }
+
+    pub fn save(&mut self, keep_backup: bool) -> Result<(), Error> {
+        self.buffer.borrow_mut().save(keep_backup)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        self.buffer.borrow().encode()
+    }
+
+    pub fn save_as(&mut self, file_path: &str, keep_backup: bool) -> Result<(), Error> {
+        self.buffer.borrow_mut().save_as(file_path, keep_backup)
+    }
+
+    pub fn is_new_file(&self) -> bool {
+        self.buffer.borrow().is_new_file()
+    }
+
+    pub fn journal(&mut self) -> Result<(), Error> {
+        self.buffer.borrow_mut().journal()
+    }
+
+    pub fn discard_swap(&mut self) -> Result<(), Error> {
+        self.buffer.borrow_mut().discard_swap()
+    }
+
+    // Contents of a swap file left behind for the file, e.g. by a crash
+    pub fn swap(&mut self) -> Option<Vec<u8>> {
+        self.buffer.borrow_mut().swap()
+    }
+
+    pub fn recover(&mut self, contents: &[u8]) {
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.begin_step(false);
+        buffer.recover(contents);
+    }
+
+    pub fn swap_diff(&self, contents: &[u8]) -> String {
+        self.buffer.borrow().swap_diff(contents)
+    }
+
+    pub fn file_path(&self) -> Option<String> {
+        self.buffer.borrow().file_path.clone()
+    }
+
+    // Whether the buffer holds a binary file, edited byte by byte in the hex view
+    pub fn is_hex(&self) -> bool {
+        self.buffer.borrow().bytes.is_some()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.buffer.borrow().is_modified
+    }
+
+    #[cfg(test)]
+    pub fn is_large_file(&self) -> bool {
+        self.buffer.borrow().is_large_file()
+    }
+
+    // Read all of a large file in the background, to edit it once that is done
+    pub fn read_whole_file(&mut self) {
+        self.buffer.borrow_mut().read_whole_file();
+    }
+
+    pub fn take_whole_file(&mut self) -> Result<bool, Error> {
+        self.buffer.borrow_mut().take_whole_file()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.buffer.borrow().read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.buffer.borrow_mut().read_only = read_only;
+    }
+
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        self.buffer.borrow().text()
+    }
+
+    // An untouched view with nothing loaded, i.e. the welcome screen
+    pub fn is_blank(&self) -> bool {
+        let buffer = self.buffer.borrow();
+        buffer.is_empty() && buffer.file_path.is_none() && !buffer.is_modified
+    }
+
+    pub fn get_status(&self) -> DocumentStatus {
+        let buffer = self.buffer.borrow();
+        if let Some(bytes) = &buffer.bytes {
+            let layout = self.hex_layout(bytes.len());
+            return DocumentStatus {
+                file_name: buffer.file_path.clone(),
+                is_modified: buffer.is_modified,
+                total_lines: layout.height(bytes.len()),
+                current_line: layout.row_of(self.hex_caret.offset),
+                is_read_only: buffer.read_only,
+                is_binary: true,
+                compression: buffer.compression.map(Compression::name),
+                ..DocumentStatus::default()
+            };
+        }
+        DocumentStatus {
+            file_name: buffer.file_path.clone(),
+            is_modified: buffer.is_modified,
+            total_lines: buffer.height(),
+            current_line: self.caret.position.row,
+            encoding: buffer.encoding.name(),
+            line_ending: buffer.line_ending.name(),
+            has_bom: buffer.has_bom,
+            compression: buffer.compression.map(Compression::name),
+            // An empty buffer has no last line to end
+            has_final_newline: buffer.has_final_newline() || buffer.is_text_empty(),
+            is_read_only: buffer.read_only,
+            indexing_progress: buffer.indexing_progress(),
+            following: buffer
+                .is_following()
+                .then(|| self.caret.position.row.saturating_add(1) >= buffer.height()),
+            ..DocumentStatus::default()
         }
     }
 
@@ -46,30 +326,234 @@ impl View {
      * (Add any additional commands to be handled here)
      *
      */
-    pub fn handle_command(&mut self, command: Command) {
+    // Fails for edits the buffer doesn't take, see `Buffer::prepare_edit`
+    pub fn handle_command(&mut self, command: Command) -> Result<(), Error> {
+        // Typing a word is undone all at once, anything else command by command
+        let typing = matches!(command, Command::Insert(char) if !char.is_whitespace());
+        self.buffer.borrow_mut().begin_step(typing);
+        match command {
+            Command::Undo => return self.undo(false),
+            Command::Redo => return self.undo(true),
+            Command::Move(_) | Command::Dismiss => {}
+            _ => self.buffer.borrow_mut().prepare_edit()?,
+        }
+        if self.is_hex() {
+            return self.handle_hex_command(&command);
+        }
         match command {
             Command::Move(direction) => self.move_caret(&direction),
-            Command::Resize(new_size) => self.terminal_resize(new_size),
-            Command::Insert(char) => self.insert(char),
-            Command::Delete => self.delete(),
-            Command::Remove => self.remove(),
+            Command::Insert(char) => self.insert(char)?,
+            Command::Delete => self.delete()?,
+            Command::Remove => self.remove()?,
+            Command::RemoveCodePoint => self.remove_code_point()?,
+            Command::Enter => self.insert_newline()?,
+            Command::Indent => self.indent()?,
+            Command::Outdent => self.outdent()?,
             _ => {}
         }
+        Ok(())
+    }
+
+    // Bytes are only ever overwritten in the hex view, so they keep their offsets
+    fn handle_hex_command(&mut self, command: &Command) -> Result<(), Error> {
+        let len = self.buffer.borrow().bytes.as_ref().map_or(0, Vec::len);
+        let layout = self.hex_layout(len);
+        match command {
+            Command::Move(direction) => {
+                self.hex_caret
+                    .move_caret(direction, layout, len, self.area.size.height);
+            }
+            Command::Insert(char) => {
+                let Some(digit) = char.to_digit(16).and_then(|digit| u8::try_from(digit).ok())
+                else {
+                    return Ok(());
+                };
+                let mut buffer = self.buffer.borrow_mut();
+                let offset = self.hex_caret.offset;
+                if let Some(byte) = buffer.bytes.as_ref().and_then(|bytes| bytes.get(offset)) {
+                    let value = hex::set_nibble(*byte, self.hex_caret.low_nibble, digit);
+                    buffer.set_byte(offset, value)?;
+                    self.hex_caret.advance(len);
+                }
+            }
+            _ => return Ok(()),
+        }
+        self.adjust_hex_scroll(layout);
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Undo the last step of edits (or redo the last one undone), and move the caret to where it
+    // happened
+    fn undo(&mut self, redo: bool) -> Result<(), Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        let position = if redo { buffer.redo()? } else { buffer.undo()? };
+        let Some(position) = position else {
+            let message = if redo {
+                "Nothing to redo."
+            } else {
+                "Nothing to undo."
+            };
+            return Err(Error::other(message));
+        };
+        if buffer.bytes.is_some() {
+            self.hex_caret = HexCaret {
+                offset: position,
+                low_nibble: false,
+            };
+            let len = buffer.bytes.as_ref().map_or(0, Vec::len);
+            drop(buffer);
+            self.adjust_hex_scroll(self.hex_layout(len));
+        } else {
+            let (row, char_offset) = buffer.char_position(position);
+            self.caret.position.row = row;
+            self.caret.move_to_char_offset(char_offset, &mut buffer);
+            drop(buffer);
+            self.adjust_screen_to_offset();
+        }
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Move the hex caret to the next occurrence of a byte pattern (see `hex::parse_pattern`)
+    pub fn find_bytes(&mut self, pattern: &str) -> Result<usize, String> {
+        let pattern = hex::parse_pattern(pattern)?;
+        let found = {
+            let buffer = self.buffer.borrow();
+            let bytes = buffer.bytes.as_deref().unwrap_or_default();
+            hex::find(bytes, &pattern, self.hex_caret.offset)
+        };
+        let offset = found.ok_or_else(|| "Pattern not found.".to_string())?;
+        self.hex_caret = HexCaret {
+            offset,
+            low_nibble: false,
+        };
+        let len = self.buffer.borrow().bytes.as_ref().map_or(0, Vec::len);
+        self.adjust_hex_scroll(self.hex_layout(len));
+        self.needs_redraw = true;
+        Ok(offset)
+    }
+
+    fn hex_layout(&self, len: usize) -> HexLayout {
+        HexLayout::new(len, self.area.size.width)
+    }
+
+    // Switch between LF and CRLF line breaks, returning the new style
+    pub fn convert_line_endings(&mut self) -> Result<&'static str, Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.prepare_edit()?;
+        buffer.begin_step(false);
+        let line_ending = match buffer.line_ending {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        };
+        buffer.set_line_ending(line_ending)?;
+        Ok(line_ending.name())
+    }
+
+    // Add the final newline if the last line has none, remove it otherwise.
+    // Returns whether the buffer now ends with a newline.
+    pub fn toggle_final_newline(&mut self) -> Result<bool, Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.prepare_edit()?;
+        buffer.begin_step(false);
+        if buffer.has_final_newline() {
+            buffer.remove_final_newline()?;
+        } else {
+            buffer.add_final_newline()?;
+        }
+        Ok(buffer.has_final_newline())
+    }
+
+    // Code points of the grapheme under the caret, for telling apart look-alike characters
+    pub fn inspect_caret(&self) -> String {
+        let mut buffer = self.buffer.borrow_mut();
+        if let Some(bytes) = &buffer.bytes {
+            let offset = self.hex_caret.offset;
+            return bytes.get(offset).map_or_else(
+                || "End of file".to_string(),
+                |byte| {
+                    format!("Offset {offset} (0x{offset:X}) | 0x{byte:02X} | {byte} | 0b{byte:08b}")
+                },
+            );
+        }
+        let Some(grapheme) = buffer
+            .line(self.caret.position.row)
+            .and_then(|line| line.grapheme(self.caret.line_location))
+            .map(str::to_string)
+        else {
+            return "End of line".to_string();
+        };
+
+        let code_points: Vec<String> = grapheme
+            .chars()
+            .map(|c| format!("U+{:04X}", u32::from(c)))
+            .collect();
+        let bytes: Vec<String> = grapheme.bytes().map(|byte| format!("{byte:02X}")).collect();
+        format!(
+            "{} | UTF-8 {} | width {}",
+            code_points.join(" "),
+            bytes.join(" "),
+            grapheme_width(&grapheme)
+        )
     }
 
+    // Caret location on the screen, taking the area the view is rendered into into account
     pub fn get_position(&self) -> Position {
-        self.caret.position.substract(&self.scroll_offset)
+        let len = self.buffer.borrow().bytes.as_ref().map(Vec::len);
+        if let Some(len) = len {
+            let layout = self.hex_layout(len);
+            let position = Position {
+                row: layout.row_of(self.hex_caret.offset),
+                col: layout.caret_col(self.hex_caret),
+            };
+            return self.area.to_screen(&position.substract(&Position {
+                row: self.scroll_offset.row,
+                col: 0,
+            }));
+        }
+        self.area
+            .to_screen(&self.caret.position.substract(&self.scroll_offset))
     }
 
     /**
      * Screen Rendering
      */
-    pub fn render(&mut self) {
+    pub fn render(&mut self, whitespace: Option<WhitespaceGlyphs>) {
+        if whitespace != self.whitespace {
+            self.whitespace = whitespace;
+            self.needs_redraw = true;
+        }
+
+        // Another view sharing the buffer may have edited it since the last render, or more
+        // lines of a large file were found
+        self.buffer.borrow_mut().sync_line_index();
+        let revision = self.buffer.borrow().revision;
+        if revision != self.seen_revision {
+            self.seen_revision = revision;
+            let mut buffer = self.buffer.borrow_mut();
+            // A followed file keeps the caret on its last line as it grows, unless the caret
+            // was moved off it to look at something
+            if buffer.is_following()
+                && self.caret.position.row.saturating_add(1) >= self.seen_height
+            {
+                self.caret.move_caret(&Direction::PageDown, &mut buffer);
+            }
+            self.seen_height = buffer.height();
+            self.caret.clamp(&mut buffer);
+            drop(buffer);
+            self.adjust_screen_to_offset();
+            self.needs_redraw = true;
+        }
+
         if !self.needs_redraw {
             return;
         }
 
-        if self.buffer.is_empty() {
+        if self.is_hex() {
+            self.render_hex();
+        } else if self.is_blank() {
+            // Once something was typed, it is a scratch buffer, even if all of it is deleted
             self.render_welcome();
         } else {
             self.render_buffer();
@@ -78,16 +562,13 @@ impl View {
     }
 
     fn render_line(&self, row_index: usize, line_content: &str) {
-        let print_res = Terminal::print_row(row_index, line_content);
-        debug_assert!(print_res.is_ok(), "Failed to print row!");
+        Terminal::print_row(&self.area, row_index, line_content);
     }
 
     fn render_welcome(&self) {
-        let terminal_size: Size = Terminal::size().unwrap_or_default();
-
-        for curr_row in 0..terminal_size.height {
+        for curr_row in 0..self.area.size.height {
             #[allow(clippy::integer_division)]
-            if curr_row == terminal_size.height / 3 {
+            if curr_row == self.area.size.height / 3 {
                 self.draw_greet_message(curr_row);
                 continue;
             }
@@ -95,38 +576,95 @@ impl View {
         }
     }
 
-    fn render_buffer(&mut self) {
-        for curr_row in 0..self.terminal_size.height {
-            if let Some(curr_line) = self
-                .buffer
-                .lines
-                .get(curr_row.saturating_add(self.scroll_offset.row))
-            {
+    fn render_buffer(&self) {
+        let mut buffer = self.buffer.borrow_mut();
+        for curr_row in 0..self.area.size.height {
+            if let Some(curr_line) = buffer.line(curr_row.saturating_add(self.scroll_offset.row)) {
                 let left = self.scroll_offset.col;
-                let right = self
-                    .scroll_offset
-                    .col
-                    .saturating_add(self.terminal_size.width);
-                let truncated_string = &curr_line.get_visible_graphemes(left..right);
-                self.render_line(curr_row, truncated_string);
+                let right = self.scroll_offset.col.saturating_add(self.area.size.width);
+                let mut spans: Vec<(String, Style)> = curr_line
+                    .get_visible_graphemes(left..right)
+                    .into_iter()
+                    .map(|span| self.render_span(span))
+                    .collect();
+                let line_end = curr_line.get_total_width();
+                if let Some(glyphs) = self.whitespace {
+                    if (left..right).contains(&line_end) {
+                        spans.push((glyphs.eol.to_string(), WHITESPACE_STYLE));
+                    }
+                }
+                Terminal::print_spans(&self.area, curr_row, &spans);
             } else {
                 self.render_line(curr_row, "~");
             }
         }
     }
 
+    fn render_hex(&self) {
+        let buffer = self.buffer.borrow();
+        let bytes = buffer.bytes.as_deref().unwrap_or_default();
+        let layout = self.hex_layout(bytes.len());
+        for curr_row in 0..self.area.size.height {
+            let row = curr_row.saturating_add(self.scroll_offset.row);
+            if row < layout.height(bytes.len()) {
+                Terminal::print_spans(&self.area, curr_row, &layout.render_row(bytes, row));
+            } else {
+                self.render_line(curr_row, "~");
+            }
+        }
+    }
+
+    // Characters that would otherwise be invisible stand out from the text around them,
+    // as does whitespace while it is being shown
+    fn render_span(&self, span: Span) -> (String, Style) {
+        let Span {
+            text,
+            kind,
+            trailing,
+        } = span;
+
+        if let (Some(glyphs), true) = (self.whitespace, kind.is_whitespace()) {
+            let glyph = match kind {
+                GraphemeKind::Space => glyphs.space,
+                GraphemeKind::Tab => glyphs.tab,
+                _ => glyphs.nbsp,
+            };
+            let style = Style {
+                background: trailing.then_some(Color::Red),
+                ..WHITESPACE_STYLE
+            };
+            return (glyph.to_string().repeat(text.chars().count()), style);
+        }
+
+        let foreground = match kind {
+            GraphemeKind::Control => Some(Color::Cyan),
+            GraphemeKind::ZeroWidth => Some(Color::Magenta),
+            GraphemeKind::Bidi => Some(Color::Red),
+            _ => None,
+        };
+        let style = Style {
+            reverse: kind == GraphemeKind::Bidi,
+            foreground,
+            ..Style::default()
+        };
+        (text, style)
+    }
+
     fn draw_greet_message(&self, row_index: usize) {
         let mut message: String = format!("{NAME} editor -- version {VERSION}");
         #[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
         let spaces: String = " ".repeat(
-            (self.terminal_size.width - message.len())
+            self.area
+                .size
+                .width
+                .saturating_sub(message.len())
                 .saturating_div(2)
                 .saturating_sub(1),
         );
-        message = format!("~{spaces}{message}\r\n");
+        message = format!("~{spaces}{message}");
 
-        if message.len() > self.terminal_size.width {
-            message = message[0..self.terminal_size.width].to_string();
+        if message.len() > self.area.size.width {
+            message = message[0..self.area.size.width].to_string();
         }
         self.render_line(row_index, &message);
     }
@@ -135,20 +673,20 @@ impl View {
         // Adjusting screen offset (overflow of text beyond the screen) based on caret movement
         // Horizontal offset
         if self.caret.position.col < self.scroll_offset.col.saturating_add(4) {
-            self.scroll_offset.col = self.scroll_offset.col.saturating_sub(1);
+            self.scroll_offset.col = self.caret.position.col.saturating_sub(4);
             self.needs_redraw = true;
         } else if self.caret.position.col
             >= self
                 .scroll_offset
                 .col
-                .saturating_add(self.terminal_size.width)
+                .saturating_add(self.area.size.width)
                 .saturating_sub(1)
         {
             self.scroll_offset.col = self
                 .caret
                 .position
                 .col
-                .saturating_sub(self.terminal_size.width)
+                .saturating_sub(self.area.size.width)
                 .saturating_add(2);
             self.needs_redraw = true;
         }
@@ -158,62 +696,253 @@ impl View {
             self.scroll_offset.row = self.caret.position.row;
             self.needs_redraw = true;
         } else if self.caret.position.row
-            >= self
-                .scroll_offset
-                .row
-                .saturating_add(self.terminal_size.height)
+            >= self.scroll_offset.row.saturating_add(self.area.size.height)
         {
             self.scroll_offset.row = self
                 .caret
                 .position
                 .row
-                .saturating_sub(self.terminal_size.height)
+                .saturating_sub(self.area.size.height)
                 .saturating_add(1);
             self.needs_redraw = true;
         }
     }
 
+    fn adjust_hex_scroll(&mut self, layout: HexLayout) {
+        let row = layout.row_of(self.hex_caret.offset);
+        if row < self.scroll_offset.row {
+            self.scroll_offset.row = row;
+        } else if row >= self.scroll_offset.row.saturating_add(self.area.size.height) {
+            self.scroll_offset.row = row.saturating_sub(self.area.size.height).saturating_add(1);
+        }
+    }
+
     /**
      * Command Operations
      */
     pub fn move_caret(&mut self, direction: &Direction) {
-        self.caret.move_caret(direction, &self.buffer);
+        self.caret
+            .move_caret(direction, &mut self.buffer.borrow_mut());
         self.adjust_screen_to_offset();
     }
 
-    pub fn terminal_resize(&mut self, new_size: Size) {
-        self.terminal_size = new_size;
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+        let len = self.buffer.borrow().bytes.as_ref().map(Vec::len);
+        if let Some(len) = len {
+            // The number of bytes per row depends on the width
+            self.adjust_hex_scroll(self.hex_layout(len));
+        } else {
+            self.adjust_screen_to_offset();
+        }
         self.needs_redraw = true;
     }
 
-    pub fn insert(&mut self, inserted_char: char) {
-        self.buffer.insert(
+    // Brackets and quotes are typed in pairs, see `pairs::typed`
+    pub fn insert(&mut self, inserted_char: char) -> Result<(), Error> {
+        let (before, after) = self.chars_around_caret();
+        let line = self.buffer.borrow().line_string(self.caret.position.row);
+        match pairs::typed(inserted_char, &line, before, after) {
+            Typed::TypeOver => self.move_caret(&Direction::Right),
+            Typed::InsertPair(closer) => {
+                self.insert_char(inserted_char)?;
+                self.insert_char(closer)?;
+                self.move_caret(&Direction::Left);
+            }
+            Typed::Insert => {
+                if self.closes_block(inserted_char) {
+                    self.outdent()?;
+                }
+                self.insert_char(inserted_char)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_char(&mut self, inserted_char: char) -> Result<(), Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        let char_offset = self.caret.char_offset(&mut buffer);
+        buffer.insert(
             inserted_char,
             self.caret.position.row,
             self.caret.line_location,
-        );
-        self.move_caret(&Direction::Right);
+        )?;
+        // The typed char may have joined the grapheme in front of it (e.g. a combining mark),
+        // so the caret is placed by char offset rather than moved one grapheme to the right
+        self.caret
+            .move_to_char_offset(char_offset.saturating_add(1), &mut buffer);
+        drop(buffer);
+        self.adjust_screen_to_offset();
         self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Removing the opener of an empty pair removes its closer as well
+    pub fn remove(&mut self) -> Result<(), Error> {
+        let (before, after) = self.chars_around_caret();
+        if pairs::is_empty_pair(before, after) {
+            self.delete()?;
+        }
+        self.remove_with(Buffer::remove)
+    }
+
+    // The chars right in front of and after the caret, within its line
+    fn chars_around_caret(&self) -> (Option<char>, Option<char>) {
+        let mut buffer = self.buffer.borrow_mut();
+        let char_offset = self.caret.char_offset(&mut buffer);
+        let line = buffer.line_string(self.caret.position.row);
+        let mut chars = line.chars().skip(char_offset.saturating_sub(1));
+        if char_offset == 0 {
+            return (None, chars.next());
+        }
+        (chars.next(), chars.next())
+    }
+
+    pub fn remove_code_point(&mut self) -> Result<(), Error> {
+        self.remove_with(Buffer::remove_code_point)
     }
 
-    pub fn remove(&mut self) {
+    fn remove_with(
+        &mut self,
+        remove: fn(&mut Buffer, usize, usize) -> Result<(), Error>,
+    ) -> Result<(), Error> {
         self.needs_redraw = true;
 
         // In case we need to merge the previous line with the current line, position the caret first then merge.
-        if self.caret.line_location == 0 && self.caret.position.row > 0 {
-            self.move_caret(&Direction::Left);
-            self.buffer
-                .merge_next_line(self.caret.position.row.saturating_sub(1));
-            return;
+        if self.caret.line_location == 0 {
+            if self.caret.position.row > 0 {
+                self.move_caret(&Direction::Left);
+                let mut buffer = self.buffer.borrow_mut();
+                let char_offset = self.caret.char_offset(&mut buffer);
+                buffer.merge_next_line(self.caret.position.row)?;
+                self.caret.move_to_char_offset(char_offset, &mut buffer);
+            }
+            return Ok(());
         }
-        self.buffer
-            .remove(self.caret.position.row, self.caret.line_location);
-        self.move_caret(&Direction::Left);
+
+        let mut buffer = self.buffer.borrow_mut();
+        let row = self.caret.position.row;
+        let char_offset = self.caret.char_offset(&mut buffer);
+        let line_chars = buffer.line_chars(row);
+        remove(&mut buffer, row, self.caret.line_location)?;
+        // Whatever was removed was in front of the caret
+        let removed_chars = line_chars.saturating_sub(buffer.line_chars(row));
+        self.caret
+            .move_to_char_offset(char_offset.saturating_sub(removed_chars), &mut buffer);
+        drop(buffer);
+        self.adjust_screen_to_offset();
+        Ok(())
     }
 
-    pub fn delete(&mut self) {
-        self.buffer
-            .delete(self.caret.position.row, self.caret.line_location);
+    pub fn delete(&mut self) -> Result<(), Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        let char_offset = self.caret.char_offset(&mut buffer);
+        buffer.delete(self.caret.position.row, self.caret.line_location)?;
+        self.caret.move_to_char_offset(char_offset, &mut buffer);
         self.needs_redraw = true;
+        Ok(())
+    }
+
+    /**
+     * Indentation
+     */
+    // Break the line at the caret. The new line keeps the indentation of this one, with a level
+    // more after a line opening a block (for file types the editor knows). If the caret was
+    // right in front of the block's closer, that goes on a line of its own below.
+    pub fn insert_newline(&mut self) -> Result<(), Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        let row = self.caret.position.row;
+        let line = buffer.line_string(row);
+        let split = line
+            .char_indices()
+            .nth(self.caret.char_offset(&mut buffer))
+            .map_or(line.len(), |(index, _)| index);
+        let (before, after) = line.split_at(split);
+
+        let mut indentation = indentation::leading_whitespace(before).to_string();
+        let opened_block = buffer
+            .file_path
+            .as_deref()
+            .and_then(IndentRules::for_path)
+            .and_then(|rules| rules.opened_block(before));
+        if let Some((_, closer)) = opened_block {
+            if closer.is_some_and(|closer| after.trim_start().starts_with(closer)) {
+                buffer.insert_line_break(row, self.caret.line_location, &indentation)?;
+            }
+            indentation.push_str(&Indentation::current().unit());
+        }
+        buffer.insert_line_break(row, self.caret.line_location, &indentation)?;
+
+        self.caret.position.row = row.saturating_add(1);
+        self.caret
+            .move_to_char_offset(indentation.chars().count(), &mut buffer);
+        drop(buffer);
+        self.adjust_screen_to_offset();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Indent the line of the caret by one level, see `Indentation`
+    pub fn indent(&mut self) -> Result<(), Error> {
+        let unit = Indentation::current().unit();
+        let mut buffer = self.buffer.borrow_mut();
+        let char_offset = self.caret.char_offset(&mut buffer);
+        buffer.insert_str(self.caret.position.row, 0, &unit)?;
+        self.caret.move_to_char_offset(
+            char_offset.saturating_add(unit.chars().count()),
+            &mut buffer,
+        );
+        drop(buffer);
+        self.adjust_screen_to_offset();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    pub fn outdent(&mut self) -> Result<(), Error> {
+        let mut buffer = self.buffer.borrow_mut();
+        let row = self.caret.position.row;
+        let len = Indentation::current().outdent_len(&buffer.line_string(row));
+        if len == 0 {
+            return Ok(());
+        }
+        let char_offset = self.caret.char_offset(&mut buffer);
+        buffer.remove_chars(row, 0, len)?;
+        self.caret
+            .move_to_char_offset(char_offset.saturating_sub(len), &mut buffer);
+        drop(buffer);
+        self.adjust_screen_to_offset();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Whether typing the char closes a block on a line with nothing but indentation in front of
+    // the caret, which then loses a level to line up with the line that opened the block. That is
+    // the closest line above indented less, and it has to end in the opener of this closer.
+    fn closes_block(&self, char: char) -> bool {
+        let mut buffer = self.buffer.borrow_mut();
+        let Some(rules) = buffer.file_path.as_deref().and_then(IndentRules::for_path) else {
+            return false;
+        };
+        if !rules.is_closer(char) {
+            return false;
+        }
+        let row = self.caret.position.row;
+        let line = buffer.line_string(row);
+        let before: String = line
+            .chars()
+            .take(self.caret.char_offset(&mut buffer))
+            .collect();
+        if before.is_empty() || indentation::leading_whitespace(&before) != before {
+            return false;
+        }
+        (0..row)
+            .rev()
+            .map(|row| buffer.line_string(row))
+            .find(|line| {
+                !line.trim().is_empty()
+                    && indentation::leading_whitespace(line).len() < before.len()
+            })
+            .and_then(|line| rules.opened_block(&line))
+            .is_some_and(|(_, closer)| closer == Some(char))
     }
 }
@@ -1,74 +1,1075 @@
 use super::line::Line;
-use std::{fs::read_to_string, io::Error};
+use disk_state::DiskState;
+use encoding_rs::{Encoding, UTF_8};
+use follow::{Follow, Growth};
+use history::{Change, History};
+use large_file::LargeFile;
+use ropey::Rope;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Error, ErrorKind, IsTerminal, Read},
+    ops::Range,
+};
 
+mod atomic_write;
+mod compression;
+mod diff;
+mod disk_state;
+mod encoding;
+mod follow;
+mod history;
+mod large_file;
+mod swap;
+
+pub use compression::Compression;
+pub use large_file::set_threshold as set_large_file_threshold;
+
+// Upper bound on how many segmented lines are kept around, so scrolling through
+// a huge file doesn't end up segmenting all of it into memory
+const MAX_CACHED_LINES: usize = 4096;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+        }
+    }
+}
+
+// Text lives in a rope, and lines are only segmented into graphemes once they are looked at
+#[allow(clippy::struct_excessive_bools)]
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    text: Rope,
+    line_cache: HashMap<usize, Line>,
+    pub file_path: Option<String>,
+    pub is_modified: bool,
+    // Incremented on every edit, so views sharing this buffer can tell it changed
+    pub revision: usize,
+    // Style of the first line break in the file, used for new ones
+    pub line_ending: LineEnding,
+    pub encoding: &'static Encoding,
+    pub has_bom: bool,
+    // Format the file is compressed in: it is decompressed on load, and compressed again on save
+    pub compression: Option<Compression>,
+    // Whether some bytes in the file were invalid in its encoding, and were replaced
+    pub had_decode_errors: bool,
+    // Contents of a binary file, which are edited as bytes (in the hex view) instead of text
+    pub bytes: Option<Vec<u8>>,
+    // A file too large to read into the rope, which is only viewed
+    large_file: Option<LargeFile>,
+    // Lines of the large file the views were told about, see `sync_line_index`
+    indexed_lines: usize,
+    // Edits are refused, e.g. because the file can't be written anyway
+    pub read_only: bool,
+    // Revision last written to the swap file, if it is up to date
+    journaled_revision: Option<usize>,
+    // The file as it was last read or written, to tell when another program changed it
+    disk_state: Option<DiskState>,
+    // The file is read as other programs append to it, see `read_appended`
+    follow: Option<Follow>,
+    // Edits that can be undone and redone
+    history: History,
 }
 
 impl Default for Buffer {
     fn default() -> Self {
-        Self { lines: Vec::new() }
+        Self {
+            text: Rope::default(),
+            line_cache: HashMap::default(),
+            file_path: None,
+            is_modified: false,
+            revision: 0,
+            line_ending: LineEnding::default(),
+            encoding: UTF_8,
+            has_bom: false,
+            compression: None,
+            had_decode_errors: false,
+            bytes: None,
+            large_file: None,
+            indexed_lines: 0,
+            read_only: false,
+            journaled_revision: None,
+            disk_state: None,
+            follow: None,
+            history: History::default(),
+        }
     }
 }
 
 impl Buffer {
+    // A buffer not backed by a file, e.g. to show a diff in
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            text: Rope::from_str(text),
+            ..Self::default()
+        }
+    }
+
     pub fn load(file_path: &str) -> Result<Self, Error> {
-        let file_to_string = read_to_string(file_path)?;
-        let mut lines: Vec<Line> = Vec::new();
-        for line in file_to_string.lines() {
-            lines.push(Line::from(line));
+        Self::load_with_encoding(file_path, None)
+    }
+
+    // A buffer for a file that doesn't exist yet, created once it is saved (compressed, if its
+    // name says so)
+    pub fn new_file(file_path: &str) -> Self {
+        Self {
+            file_path: Some(file_path.to_string()),
+            compression: Compression::for_path(file_path),
+            ..Self::default()
+        }
+    }
+
+    // Load a file in the given encoding, or in the one it looks like it is in
+    pub fn load_with_encoding(
+        file_path: &str,
+        encoding: Option<&'static Encoding>,
+    ) -> Result<Self, Error> {
+        if encoding.is_none() && fs::metadata(file_path)?.len() >= large_file::threshold() {
+            if let Some(large_file) = LargeFile::open(file_path)? {
+                return Ok(Self::from_large_file(file_path, large_file));
+            }
         }
-        lines.push(Line::from(""));
 
-        Ok(Self { lines })
+        let bytes = fs::read(file_path)?;
+        let disk_state = DiskState::new(file_path, &bytes);
+        let (bytes, compression) = decompress(bytes)?;
+        Ok(Self {
+            file_path: Some(file_path.to_string()),
+            compression,
+            disk_state: Some(disk_state),
+            read_only: !is_writable(file_path),
+            ..Self::from_bytes(bytes, encoding)
+        })
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.lines.len() == 0
+    // Large files aren't watched for changes by other programs, as that would mean hashing all
+    // of them, until they are read in whole to be edited (see `read_whole_file`)
+    fn from_large_file(file_path: &str, large_file: LargeFile) -> Self {
+        Self {
+            file_path: Some(file_path.to_string()),
+            line_ending: if large_file.has_crlf() {
+                LineEnding::Crlf
+            } else {
+                LineEnding::Lf
+            },
+            encoding: large_file.encoding,
+            has_bom: large_file.has_bom(),
+            read_only: !is_writable(file_path),
+            large_file: Some(large_file),
+            ..Self::default()
+        }
     }
 
-    pub fn merge_next_line(&mut self, current_row_index: usize) {
-        // Merging line of index 'current_row_index' with the next line
-        if current_row_index.saturating_add(1) >= self.lines.len() {
+    // Read everything piped into the editor, e.g. `ls | script-pad -`
+    pub fn read_stdin() -> Result<Self, Error> {
+        let mut stdin = io::stdin();
+        if stdin.is_terminal() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "nothing is piped into stdin",
+            ));
+        }
+        let mut bytes = Vec::new();
+        stdin.read_to_end(&mut bytes)?;
+        let (bytes, compression) = decompress(bytes)?;
+        Ok(Self {
+            compression,
+            ..Self::from_bytes(bytes, None)
+        })
+    }
+
+    // Decode the contents of a file, unless they look binary and no encoding was asked for
+    fn from_bytes(bytes: Vec<u8>, encoding: Option<&'static Encoding>) -> Self {
+        if encoding.is_none() && encoding::is_binary(&bytes) {
+            return Self {
+                bytes: Some(bytes),
+                ..Self::default()
+            };
+        }
+
+        let decoded = encoding::decode(&bytes, encoding);
+        let text = Rope::from_str(&decoded.text);
+
+        let line_ending = if text.len_lines() > 1 && text.line(0).to_string().ends_with("\r\n") {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        };
+
+        Self {
+            text,
+            line_ending,
+            encoding: decoded.encoding,
+            has_bom: decoded.has_bom,
+            had_decode_errors: decoded.had_errors,
+            ..Self::default()
+        }
+    }
+
+    // Nothing is written unless all of it can be, see `atomic_write::write`
+    pub fn save(&mut self, keep_backup: bool) -> Result<(), Error> {
+        let Some(file_path) = &self.file_path else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+
+        // Encode everything before touching the file, so a failure leaves it as it was
+        let bytes = self.encode()?;
+        atomic_write::write(file_path, &bytes, keep_backup)?;
+        self.disk_state = Some(DiskState::new(file_path, &bytes));
+        self.is_modified = false;
+        self.history.mark_saved();
+        // Nothing is left to recover once the changes are in the file itself
+        let _ = self.discard_swap();
+        Ok(())
+    }
+
+    // The bytes saving would write: the text in the encoding of the file, or the bytes of a
+    // binary one, compressed the way the file was
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let bytes = self.encode_uncompressed()?;
+        match self.compression {
+            Some(compression) => compression.compress(&bytes),
+            None => Ok(bytes),
+        }
+    }
+
+    fn encode_uncompressed(&self) -> Result<Vec<u8>, Error> {
+        if let Some(bytes) = &self.bytes {
+            return Ok(bytes.clone());
+        }
+        if let Some(large_file) = &self.large_file {
+            return large_file.read_all();
+        }
+        encoding::encode(self.text.chunks(), self.encoding, self.has_bom).map_err(|unencodable| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "'{}' (U+{:04X}) on line {} cannot be encoded as {}",
+                    unencodable.character,
+                    u32::from(unencodable.character),
+                    self.text.byte_to_line(unencodable.offset).saturating_add(1),
+                    self.encoding.name()
+                ),
+            )
+        })
+    }
+
+    // Save to another file, which the buffer is about from then on. If that fails, it stays
+    // about the file it was before. The new name decides whether it is compressed, and how.
+    pub fn save_as(&mut self, file_path: &str, keep_backup: bool) -> Result<(), Error> {
+        if self.file_path.as_deref() == Some(file_path) {
+            return self.save(keep_backup);
+        }
+        let previous_path = self.file_path.replace(file_path.to_string());
+        let previous_state = self.disk_state.take();
+        let previous_compression = self.compression;
+        self.compression = Compression::for_path(file_path);
+        if let Err(err) = self.save(keep_backup) {
+            self.file_path = previous_path;
+            self.disk_state = previous_state;
+            self.compression = previous_compression;
+            return Err(err);
+        }
+        // The changes went into the new file, leaving nothing to recover for the old one
+        if let Some(previous_path) = previous_path {
+            let _ = swap::remove(&previous_path);
+        }
+        Ok(())
+    }
+
+    // Whether the buffer was never read from or written to its file
+    pub fn is_new_file(&self) -> bool {
+        self.file_path.is_some() && self.disk_state.is_none() && self.large_file.is_none()
+    }
+
+    #[cfg(test)]
+    pub fn is_large_file(&self) -> bool {
+        self.large_file.is_some()
+    }
+
+    // How far finding the lines of a large file got, in percent, while it is still going on
+    pub fn indexing_progress(&self) -> Option<usize> {
+        self.large_file
+            .as_ref()
+            .and_then(LargeFile::indexing_progress)
+    }
+
+    // Let the views know when more lines of a large file were found since they last looked
+    pub fn sync_line_index(&mut self) {
+        // Edits to the rope bump the revision on their own
+        let Some(large_file) = &self.large_file else {
+            return;
+        };
+        let height = large_file.height();
+        if height != self.indexed_lines {
+            self.indexed_lines = height;
+            self.revision = self.revision.wrapping_add(1);
+        }
+    }
+
+    /**
+     * Crash Recovery
+     */
+    // Write unsaved changes to the swap file, unless it already has them
+    pub fn journal(&mut self) -> Result<(), Error> {
+        let Some(file_path) = &self.file_path else {
+            return Ok(());
+        };
+        // A large file can't have changes before it is read in whole, see `contents`
+        if !self.is_modified
+            || self.journaled_revision == Some(self.revision)
+            || self.large_file.is_some()
+        {
+            return Ok(());
+        }
+        swap::write(file_path, &self.contents())?;
+        self.journaled_revision = Some(self.revision);
+        Ok(())
+    }
+
+    pub fn discard_swap(&mut self) -> Result<(), Error> {
+        self.journaled_revision = None;
+        match &self.file_path {
+            Some(file_path) => swap::remove(file_path),
+            None => Ok(()),
+        }
+    }
+
+    // Contents of a swap file left behind for this file, e.g. by a crash. A swap file with
+    // nothing the file doesn't have is of no use, and is removed.
+    pub fn swap(&mut self) -> Option<Vec<u8>> {
+        // Comparing would mean reading all of a large file, which has no changes to lose anyway:
+        // editing it reads it in whole first
+        if self.large_file.is_some() {
+            return None;
+        }
+        let contents = swap::read(self.file_path.as_deref()?)?;
+        if contents == self.contents() {
+            let _ = self.discard_swap();
+            return None;
+        }
+        Some(contents)
+    }
+
+    // Replace the contents with the ones from the swap file, leaving them unsaved
+    pub fn recover(&mut self, contents: &[u8]) {
+        if self.bytes.is_some() {
+            // Bytes are only undone one at a time, so there is no going back past the recovery
+            self.bytes = Some(contents.to_vec());
+            self.history = History::default();
+            self.history.forget_saved();
+            self.mark_modified();
+        } else {
+            self.replace(0..self.text.len_chars(), &String::from_utf8_lossy(contents));
+            self.line_cache.clear();
+        }
+        self.journaled_revision = Some(self.revision);
+    }
+
+    // Lines the swap file changes, compared to the file
+    pub fn swap_diff(&self, contents: &[u8]) -> String {
+        match &self.bytes {
+            Some(bytes) => diff::bytes(bytes, contents),
+            None => diff::lines(&self.text.to_string(), &String::from_utf8_lossy(contents)),
+        }
+    }
+
+    /**
+     * Changes by Other Programs
+     */
+    pub fn changed_on_disk(&mut self) -> bool {
+        // A followed file changes all the time, and the buffer keeps up on its own
+        if self.follow.is_some() {
+            return false;
+        }
+        match (&self.file_path, &mut self.disk_state) {
+            (Some(file_path), Some(disk_state)) => disk_state.has_changed(file_path),
+            _ => false,
+        }
+    }
+
+    /**
+     * Following a Growing File
+     */
+    // Start reading what other programs append to the file, e.g. a log. The buffer is
+    // read-only meanwhile, as its lines have to stay the ones in the file.
+    pub fn start_following(&mut self) -> Result<(), Error> {
+        if self.is_modified {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Save or reload the buffer before following its file.",
+            ));
+        }
+        if self.bytes.is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Binary files can't be followed.",
+            ));
+        }
+        if self.compression.is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Compressed files can't be followed.",
+            ));
+        }
+        // What is appended meanwhile would be missing from the text once it is read
+        if self.large_file.as_ref().is_some_and(LargeFile::is_reading) {
+            return Err(Error::new(
+                ErrorKind::WouldBlock,
+                "Still reading the file, it can be followed once that is done.",
+            ));
+        }
+        let offset = match (&self.large_file, &self.disk_state) {
+            (Some(large_file), _) => Some(u64::try_from(large_file.len()).unwrap_or(u64::MAX)),
+            (None, Some(disk_state)) => Some(disk_state.len()),
+            (None, None) => None,
+        };
+        let (Some(file_path), Some(offset)) = (&self.file_path, offset) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "Only files on disk can be followed.",
+            ));
+        };
+        self.follow = Some(Follow::new(
+            file_path,
+            offset,
+            self.encoding,
+            self.read_only,
+        ));
+        self.read_only = true;
+        Ok(())
+    }
+
+    pub fn stop_following(&mut self) {
+        let Some(follow) = self.follow.take() else {
             return;
+        };
+        self.read_only = follow.was_read_only;
+        // What was appended since loading is in the buffer, and the file is watched from here
+        if let (Some(file_path), None) = (&self.file_path, &self.large_file) {
+            self.disk_state = DiskState::read(file_path).or(self.disk_state);
+        }
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.follow.is_some()
+    }
+
+    // Add whatever was appended to the followed file since it was last looked at, or read it
+    // anew if it was replaced. Returns whether anything changed.
+    pub fn read_appended(&mut self) -> Result<bool, Error> {
+        let (Some(follow), Some(file_path)) = (&mut self.follow, &self.file_path) else {
+            return Ok(false);
+        };
+        // A large file can only take in more once the lines it has so far are all found
+        if self
+            .large_file
+            .as_ref()
+            .is_some_and(|large_file| large_file.indexing_progress().is_some())
+        {
+            return Ok(false);
+        }
+
+        match follow.poll(file_path, self.large_file.is_none())? {
+            Growth::None => return Ok(false),
+            Growth::Appended(text) => {
+                let old_height = self.height();
+                match &mut self.large_file {
+                    Some(large_file) => large_file.extend()?,
+                    None => self.text.insert(self.text.len_chars(), &text),
+                }
+                // The old last line may have grown, and the one before it may have ended in a
+                // `\r` now followed by its `\n`; the lines after them are new
+                self.line_cache
+                    .retain(|&row, _| row < old_height.saturating_sub(2));
+            }
+            Growth::Replaced => {
+                // The buffer is only swapped out once the new file can be followed, e.g. it
+                // isn't binary, so the old lines stay otherwise
+                let was_read_only = follow.was_read_only;
+                let mut buffer = Self::load(file_path)?;
+                buffer.revision = self.revision;
+                buffer.start_following()?;
+                if let Some(follow) = &mut buffer.follow {
+                    follow.was_read_only = was_read_only;
+                }
+                *self = buffer;
+            }
+        }
+        self.revision = self.revision.wrapping_add(1);
+        Ok(true)
+    }
+
+    // Take the file as it is now as the version the buffer is based on, so saving replaces it
+    // without asking again
+    pub fn keep_over_disk(&mut self) {
+        if let Some(file_path) = &self.file_path {
+            self.disk_state = DiskState::read(file_path).or(self.disk_state);
+        }
+    }
+
+    // Lines the buffer changes, compared to the file on disk
+    pub fn disk_diff(&self) -> Result<String, Error> {
+        let Some(file_path) = &self.file_path else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+        let (on_disk, _) = decompress(fs::read(file_path)?)?;
+        if let Some(bytes) = &self.bytes {
+            return Ok(diff::bytes(&on_disk, bytes));
+        }
+        let decoded = encoding::decode(&on_disk, Some(self.encoding));
+        Ok(diff::lines(&decoded.text, &self.text.to_string()))
+    }
+
+    // What is written to the swap file: the text as UTF-8, or the bytes of a binary file. Never
+    // asked of a large file, which is only journaled once it was read in whole.
+    fn contents(&self) -> Vec<u8> {
+        match &self.bytes {
+            Some(bytes) => bytes.clone(),
+            None => self.text.to_string().into_bytes(),
+        }
+    }
+
+    // Every edit goes through here first: it is refused if the buffer is read-only, or if it is
+    // a large file that wasn't read in whole yet (see `read_whole_file`)
+    pub fn prepare_edit(&mut self) -> Result<(), Error> {
+        self.check_writable()?;
+        self.take_whole_file()?;
+        match &self.large_file {
+            Some(large_file) if large_file.is_reading() => Err(Error::new(
+                ErrorKind::WouldBlock,
+                "Still reading the file, it can be edited once that is done.",
+            )),
+            Some(_) => Err(Error::new(
+                ErrorKind::FileTooLarge,
+                "The file has to be read into memory to edit it.",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Buffer is read-only, Alt-R makes it editable.",
+            ));
         }
+        Ok(())
+    }
 
-        let (current_row, next_row) = {
-            let (left, right) = self.lines.split_at_mut(current_row_index.saturating_add(1));
-            (&mut left[current_row_index], &mut right[0])
+    // Start reading a large file in whole on a background thread, after which it is edited like
+    // any other, see `take_whole_file`
+    pub fn read_whole_file(&mut self) {
+        if let Some(large_file) = &mut self.large_file {
+            large_file.start_reading();
+        }
+    }
+
+    // Take in the whole file once the background thread read it. Returns whether it did.
+    pub fn take_whole_file(&mut self) -> Result<bool, Error> {
+        let Some(whole) = self.large_file.as_mut().and_then(LargeFile::take_text) else {
+            return Ok(false);
         };
+        let whole = whole?;
+        self.text = whole.text;
+        self.had_decode_errors = whole.had_errors;
+        // Only now can changes by other programs be told apart
+        if let Some(file_path) = &self.file_path {
+            self.disk_state = Some(DiskState::with_hash(file_path, whole.hash));
+        }
+        self.large_file = None;
+        self.line_cache.clear();
+        self.revision = self.revision.wrapping_add(1);
+        Ok(true)
+    }
+
+    // Overwrite a byte of a binary file
+    pub fn set_byte(&mut self, offset: usize, value: u8) -> Result<(), Error> {
+        self.prepare_edit()?;
+        if let Some(byte) = self.bytes.as_mut().and_then(|bytes| bytes.get_mut(offset)) {
+            let old = std::mem::replace(byte, value);
+            self.history.record(Change::Byte {
+                offset,
+                old,
+                new: value,
+            });
+            self.mark_modified();
+        }
+        Ok(())
+    }
+
+    // Replace the chars in `range` with `inserted`, keeping what it takes to undo that. Lines
+    // in the cache are left for the caller to update.
+    fn replace(&mut self, range: Range<usize>, inserted: &str) {
+        let removed = self.text.slice(range.clone()).to_string();
+        self.text.remove(range.clone());
+        self.text.insert(range.start, inserted);
+        self.history.record(Change::Text {
+            at: range.start,
+            removed,
+            inserted: inserted.to_string(),
+        });
+        self.mark_modified();
+    }
+
+    fn mark_modified(&mut self) {
+        self.is_modified = true;
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.len_chars() == 0 && self.file_path.is_none()
+    }
+
+    #[cfg(test)]
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    pub fn is_text_empty(&self) -> bool {
+        if let Some(large_file) = &self.large_file {
+            return large_file.is_text_empty();
+        }
+        self.text.len_chars() == 0
+    }
+
+    pub fn has_final_newline(&self) -> bool {
+        if let Some(large_file) = &self.large_file {
+            return large_file.has_final_newline();
+        }
+        self.text.len_chars() > 0 && self.text.char(self.text.len_chars().saturating_sub(1)) == '\n'
+    }
+
+    // Rewrite every line break (whatever its style was) with the given one
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) -> Result<(), Error> {
+        self.prepare_edit()?;
+        let mut text = String::with_capacity(self.text.len_bytes());
+        for row in 0..self.height() {
+            text.push_str(&Self::line_text(&self.text, row));
+            if row.saturating_add(1) < self.height() {
+                text.push_str(line_ending.as_str());
+            }
+        }
+        self.line_ending = line_ending;
+        if self.text != text.as_str() {
+            // Lines keep their content, so the cached ones stay valid
+            self.replace(0..self.text.len_chars(), &text);
+        }
+        Ok(())
+    }
+
+    pub fn add_final_newline(&mut self) -> Result<(), Error> {
+        self.prepare_edit()?;
+        if !self.has_final_newline() {
+            let end = self.text.len_chars();
+            self.replace(end..end, self.line_ending.as_str());
+        }
+        Ok(())
+    }
+
+    pub fn remove_final_newline(&mut self) -> Result<(), Error> {
+        if self.has_final_newline() {
+            let last_row = self.height().saturating_sub(1);
+            self.merge_next_line(last_row.saturating_sub(1))?;
+        }
+        Ok(())
+    }
+
+    pub fn height(&self) -> usize {
+        self.large_file
+            .as_ref()
+            .map_or_else(|| self.text.len_lines(), LargeFile::height)
+    }
+
+    pub fn line(&mut self, row: usize) -> Option<&Line> {
+        if row >= self.height() {
+            return None;
+        }
+        if self.line_cache.len() >= MAX_CACHED_LINES && !self.line_cache.contains_key(&row) {
+            self.line_cache.clear();
+        }
+
+        // Lines are only segmented once they are looked at, which for a large file is also when
+        // they are decoded
+        let (text, large_file) = (&self.text, &self.large_file);
+        Some(self.line_cache.entry(row).or_insert_with(|| {
+            let line = match large_file {
+                Some(large_file) => large_file.line(row).unwrap_or_default(),
+                None => Self::line_text(text, row),
+            };
+            Line::from(&line)
+        }))
+    }
+
+    // Contents of a line, without its line break
+    fn line_text(text: &Rope, row: usize) -> String {
+        let mut line = text.line(row).to_string();
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        line
+    }
+
+    // Text of a line in the rope, without its line break
+    pub fn line_string(&self, row: usize) -> String {
+        if row >= self.text.len_lines() {
+            return String::new();
+        }
+        Self::line_text(&self.text, row)
+    }
+
+    // Number of chars in a line, without its line break
+    pub fn line_chars(&mut self, row: usize) -> usize {
+        self.line(row)
+            .map_or(0, |line| line.char_offset(line.len()))
+    }
+
+    // Index into the rope of the grapheme at `line_location` in the given row
+    fn char_index(&mut self, row: usize, line_location: usize) -> usize {
+        let line_start = self.text.line_to_char(row);
+        let offset = self
+            .line(row)
+            .map_or(0, |line| line.char_offset(line_location));
+        line_start.saturating_add(offset)
+    }
+
+    pub fn merge_next_line(&mut self, current_row_index: usize) -> Result<(), Error> {
+        self.prepare_edit()?;
+        // Merging line of index 'current_row_index' with the next line
+        if current_row_index.saturating_add(1) >= self.height() {
+            return Ok(());
+        }
+
+        // Remove the line break at the end of the current line
+        let next_line_start = self.text.line_to_char(current_row_index.saturating_add(1));
+        let line_length = self.line_chars(current_row_index);
+        let line_end = self
+            .text
+            .line_to_char(current_row_index)
+            .saturating_add(line_length);
+        self.replace(line_end..next_line_start, "");
 
-        current_row.append(&mut next_row.line_content);
-        self.lines.remove(current_row_index.saturating_add(1));
+        // Lines after the merged one move up by one
+        let next_line = self.line_cache.remove(&current_row_index.saturating_add(1));
+        if let (Some(current_line), Some(next_line)) =
+            (self.line_cache.get_mut(&current_row_index), next_line)
+        {
+            current_line.append(next_line);
+        } else {
+            self.line_cache.remove(&current_row_index);
+        }
+        self.line_cache = self
+            .line_cache
+            .drain()
+            .map(|(row, line)| {
+                if row > current_row_index {
+                    (row.saturating_sub(1), line)
+                } else {
+                    (row, line)
+                }
+            })
+            .collect();
+        Ok(())
     }
 
     /**
      * Command Operations
      */
-    pub fn insert(&mut self, inserted_char: char, insert_row: usize, line_insert_location: usize) {
-        self.lines[insert_row].insert(inserted_char, line_insert_location);
+    pub fn insert(
+        &mut self,
+        inserted_char: char,
+        insert_row: usize,
+        line_insert_location: usize,
+    ) -> Result<(), Error> {
+        self.prepare_edit()?;
+        let char_index = self.char_index(insert_row, line_insert_location);
+        self.replace(
+            char_index..char_index,
+            inserted_char.encode_utf8(&mut [0; 4]),
+        );
+        if let Some(line) = self.line_cache.get_mut(&insert_row) {
+            line.insert(inserted_char, line_insert_location);
+        }
+        Ok(())
     }
 
-    pub fn remove(&mut self, remove_row: usize, line_remove_location: usize) {
+    // Break a line in two at the given location, starting the new line with `indentation`
+    pub fn insert_line_break(
+        &mut self,
+        row: usize,
+        line_location: usize,
+        indentation: &str,
+    ) -> Result<(), Error> {
+        self.prepare_edit()?;
+        let char_index = self.char_index(row, line_location);
+        let inserted = format!("{}{indentation}", self.line_ending.as_str());
+        self.replace(char_index..char_index, &inserted);
+
+        // Lines after the split one move down by one
+        self.line_cache.remove(&row);
+        self.line_cache = self
+            .line_cache
+            .drain()
+            .map(|(cached_row, line)| {
+                if cached_row > row {
+                    (cached_row.saturating_add(1), line)
+                } else {
+                    (cached_row, line)
+                }
+            })
+            .collect();
+        Ok(())
+    }
+
+    // Insert text within a line at a char offset into it, e.g. indentation at its start
+    pub fn insert_str(&mut self, row: usize, char_offset: usize, text: &str) -> Result<(), Error> {
+        self.prepare_edit()?;
+        let char_index = self.text.line_to_char(row).saturating_add(char_offset);
+        self.replace(char_index..char_index, text);
+        self.line_cache.remove(&row);
+        Ok(())
+    }
+
+    // Remove chars within a line, starting at a char offset into it
+    pub fn remove_chars(
+        &mut self,
+        row: usize,
+        char_offset: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        self.prepare_edit()?;
+        let start = self.text.line_to_char(row).saturating_add(char_offset);
+        self.replace(start..start.saturating_add(len), "");
+        self.line_cache.remove(&row);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, remove_row: usize, line_remove_location: usize) -> Result<(), Error> {
         // Merge current line with previous line, if caret is currently in the beginning of a line
         if line_remove_location == 0 {
             if remove_row > 0 {
-                self.merge_next_line(remove_row.saturating_sub(1));
+                self.merge_next_line(remove_row.saturating_sub(1))?;
             }
-            return;
+            return Ok(());
         }
 
-        self.lines[remove_row].remove(line_remove_location.saturating_sub(1));
+        self.remove_grapheme(remove_row, line_remove_location.saturating_sub(1))
     }
 
-    pub fn delete(&mut self, remove_row: usize, line_delete_location: usize) {
+    pub fn delete(&mut self, remove_row: usize, line_delete_location: usize) -> Result<(), Error> {
         // Merge previous line with current line, if caret is at the end of a line
-        if line_delete_location == self.lines[remove_row].len() {
-            if remove_row.saturating_add(1) < self.lines.len() {
-                self.merge_next_line(remove_row);
+        if line_delete_location >= self.line(remove_row).map_or(0, Line::len) {
+            if remove_row.saturating_add(1) < self.height() {
+                self.merge_next_line(remove_row)?;
+            }
+            return Ok(());
+        }
+        self.remove_grapheme(remove_row, line_delete_location)
+    }
+
+    // Like `remove`, but only takes the last code point off the grapheme in front of the caret
+    pub fn remove_code_point(
+        &mut self,
+        remove_row: usize,
+        line_remove_location: usize,
+    ) -> Result<(), Error> {
+        let Some(line_location) = line_remove_location.checked_sub(1) else {
+            return self.remove(remove_row, line_remove_location);
+        };
+
+        self.prepare_edit()?;
+        let end = self.char_index(remove_row, line_remove_location);
+        self.replace(end.saturating_sub(1)..end, "");
+        if let Some(line) = self.line_cache.get_mut(&remove_row) {
+            line.remove_code_point(line_location);
+        }
+        Ok(())
+    }
+
+    fn remove_grapheme(&mut self, row: usize, line_location: usize) -> Result<(), Error> {
+        self.prepare_edit()?;
+        let start = self.char_index(row, line_location);
+        let end = self.char_index(row, line_location.saturating_add(1));
+        self.replace(start..end, "");
+        if let Some(line) = self.line_cache.get_mut(&row) {
+            line.remove(line_location);
+        }
+        Ok(())
+    }
+
+    /**
+     * Undo and Redo
+     */
+    // Called before each command, see `History::begin_step`
+    pub fn begin_step(&mut self, typing: bool) {
+        self.history.begin_step(typing);
+    }
+
+    // Take back the last step of edits. Returns where it happened, as a char index into the
+    // text or a byte offset into a binary file, or None if there is nothing left to undo.
+    pub fn undo(&mut self) -> Result<Option<usize>, Error> {
+        self.check_writable()?;
+        Ok(self.history.undo().map(|changes| self.apply(&changes)))
+    }
+
+    pub fn redo(&mut self) -> Result<Option<usize>, Error> {
+        self.check_writable()?;
+        Ok(self.history.redo().map(|changes| self.apply(&changes)))
+    }
+
+    fn apply(&mut self, changes: &[Change]) -> usize {
+        let mut position = 0;
+        for change in changes {
+            match change {
+                Change::Text {
+                    at,
+                    removed,
+                    inserted,
+                } => {
+                    self.text
+                        .remove(*at..at.saturating_add(removed.chars().count()));
+                    self.text.insert(*at, inserted);
+                }
+                Change::Byte { offset, new, .. } => {
+                    if let Some(byte) = self.bytes.as_mut().and_then(|bytes| bytes.get_mut(*offset))
+                    {
+                        *byte = *new;
+                    }
+                }
+            }
+            position = change.end();
+        }
+        self.line_cache.clear();
+        self.is_modified = !self.history.is_saved();
+        self.revision = self.revision.wrapping_add(1);
+        position
+    }
+
+    // Row and char offset into it of a char index, e.g. one `undo` returned
+    pub fn char_position(&self, char_index: usize) -> (usize, usize) {
+        let char_index = char_index.min(self.text.len_chars());
+        let row = self.text.char_to_line(char_index);
+        (row, char_index.saturating_sub(self.text.line_to_char(row)))
+    }
+}
+
+// Contents of a file as they are once decompressed, if they are compressed at all
+fn decompress(bytes: Vec<u8>) -> Result<(Vec<u8>, Option<Compression>), Error> {
+    match Compression::detect(&bytes) {
+        Some(compression) => Ok((compression.decompress(&bytes)?, Some(compression))),
+        None => Ok((bytes, None)),
+    }
+}
+
+// Whether this user may write to the file, which its permission bits alone don't tell
+#[cfg(unix)]
+fn is_writable(file_path: &str) -> bool {
+    let Ok(path) = std::ffi::CString::new(file_path) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid C string that outlives the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(file_path: &str) -> bool {
+    fs::metadata(file_path).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Buffer;
+
+    #[test]
+    fn read_only_buffers_refuse_edits() {
+        let mut buffer = Buffer::from_text("a\nb");
+        buffer.read_only = true;
+        assert!(buffer.insert('x', 0, 0).is_err());
+        assert!(buffer.merge_next_line(0).is_err());
+        assert!(buffer.insert_line_break(0, 1, "").is_err());
+        assert!(buffer.delete(0, 0).is_err());
+        assert_eq!(buffer.text(), "a\nb");
+        assert!(!buffer.is_modified);
+    }
+}
+
+// Timings for a 100 MB file, run with `cargo test --release -- --ignored --nocapture large_file`
+#[cfg(test)]
+mod benches {
+    use super::{large_file, Buffer};
+    use std::{
+        env,
+        fs::{remove_file, File},
+        io::{BufWriter, Write},
+        time::Instant,
+    };
+
+    const FILE_SIZE: usize = 100 * 1024 * 1024;
+    const LINE: &str = "Lörëm ípsüm dólör sït âmét, cönsëctetur âdïpïscïng élit. ＡＢＣ 🦀\n";
+
+    #[test]
+    #[ignore = "benchmark that writes a 100 MB file"]
+    fn large_file_load_and_edit() {
+        let file_path = env::temp_dir().join("script-pad-large-file-bench.txt");
+        let file_path = file_path.to_str().unwrap();
+        {
+            let mut file = BufWriter::new(File::create(file_path).unwrap());
+            let mut written: usize = 0;
+            while written < FILE_SIZE {
+                file.write_all(LINE.as_bytes()).unwrap();
+                written = written.saturating_add(LINE.len());
             }
-            return;
         }
-        self.lines[remove_row].delete(line_delete_location);
+
+        // Measures the rope, which large-file mode would otherwise take over from
+        large_file::set_threshold(u64::MAX);
+        let start = Instant::now();
+        let mut buffer = Buffer::load(file_path).unwrap();
+        eprintln!("load {} lines: {:?}", buffer.height(), start.elapsed());
+
+        for (name, row) in [
+            ("start", 0),
+            ("middle", buffer.height().saturating_div(2)),
+            ("end", buffer.height().saturating_sub(2)),
+        ] {
+            let start = Instant::now();
+            for location in 0..100 {
+                buffer.insert('x', row, location).unwrap();
+            }
+            for _ in 0..100 {
+                buffer.delete(row, 0).unwrap();
+            }
+            buffer.merge_next_line(row).unwrap();
+            eprintln!(
+                "100 inserts, 100 deletes and a merge at the {name}: {:?}",
+                start.elapsed()
+            );
+        }
+
+        let start = Instant::now();
+        buffer.save(false).unwrap();
+        eprintln!("save: {:?}", start.elapsed());
+
+        remove_file(file_path).unwrap();
     }
 }
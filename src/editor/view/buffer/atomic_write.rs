@@ -0,0 +1,213 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+// Replace a file with new contents so that it is always either fully old or fully new: the
+// bytes go to a temporary file next to it, which is then renamed over it. On failure the
+// temporary file is removed and the original left alone. With `keep_backup`, the previous
+// contents are kept as `file~`.
+pub fn write(path: &str, bytes: &[u8], keep_backup: bool) -> Result<(), Error> {
+    // Write through a symbolic link instead of replacing the link with a file
+    let target = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let original = fs::metadata(&target).ok();
+    let (temp_path, mut temp_file) = create_temp_file(&target)?;
+
+    let result = write_temp_file(&mut temp_file, bytes, original.as_ref())
+        .and_then(|()| {
+            if keep_backup && original.is_some() {
+                backup(&target)
+            } else {
+                Ok(())
+            }
+        })
+        .and_then(|()| {
+            fs::rename(&temp_path, &target)
+                .map_err(|err| context("Could not replace the file", &err))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Make the rename itself survive a crash; not every platform can sync a directory
+    if let Ok(directory) = File::open(parent(&target)) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+// The temporary file has to be in the same directory, as renaming only replaces the file in
+// one step within a file system
+fn create_temp_file(target: &Path) -> Result<(PathBuf, File), Error> {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for attempt in 0..100_u32 {
+        let temp_path = parent(target).join(format!(".{name}.{}.{attempt}.tmp", process::id()));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(context("Could not create a temporary file", &err)),
+        }
+    }
+    Err(Error::new(
+        ErrorKind::AlreadyExists,
+        "Could not create a temporary file: too many left over",
+    ))
+}
+
+fn write_temp_file(
+    file: &mut File,
+    bytes: &[u8],
+    original: Option<&fs::Metadata>,
+) -> Result<(), Error> {
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .map_err(|err| context("Could not write the file", &err))?;
+
+    if let Some(original) = original {
+        file.set_permissions(original.permissions())
+            .map_err(|err| context("Could not keep the file permissions", &err))?;
+        keep_owner(file, original)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn keep_owner(file: &File, original: &fs::Metadata) -> Result<(), Error> {
+    use std::os::unix::fs::{fchown, MetadataExt};
+
+    let current = file.metadata()?;
+    if (current.uid(), current.gid()) == (original.uid(), original.gid()) {
+        return Ok(());
+    }
+    // Only root can give a file away; anyone else saving someone else's (writable) file
+    // ends up owning it, as with any editor
+    match fchown(file, Some(original.uid()), Some(original.gid())) {
+        Err(err) if err.kind() != ErrorKind::PermissionDenied => {
+            Err(context("Could not keep the file owner", &err))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn keep_owner(_file: &File, _original: &fs::Metadata) -> Result<(), Error> {
+    Ok(())
+}
+
+// A hard link keeps the original contents under the backup name once the file is replaced,
+// without copying them; file systems without links get a copy
+fn backup(target: &Path) -> Result<(), Error> {
+    let mut backup_path = target.as_os_str().to_owned();
+    backup_path.push("~");
+    let backup_path = PathBuf::from(backup_path);
+
+    match fs::remove_file(&backup_path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            return Err(context("Could not replace the backup", &err))
+        }
+        _ => {}
+    }
+    fs::hard_link(target, &backup_path)
+        .or_else(|_| fs::copy(target, &backup_path).map(|_| ()))
+        .map_err(|err| context("Could not write the backup", &err))
+}
+
+fn context(stage: &str, err: &Error) -> Error {
+    Error::new(err.kind(), format!("{stage}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write;
+    use std::{env, fs, path::PathBuf, process};
+
+    fn scratch_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("script-pad-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir(&directory).unwrap();
+        directory
+    }
+
+    fn entries(directory: &PathBuf) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn replaces_the_file_and_keeps_a_backup() {
+        let directory = scratch_directory("atomic");
+        let path = directory.join("notes.txt");
+        let path_str = path.to_string_lossy().into_owned();
+
+        write(&path_str, b"first", false).unwrap();
+        write(&path_str, b"second", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(fs::read(directory.join("notes.txt~")).unwrap(), b"first");
+
+        write(&path_str, b"third", true).unwrap();
+        assert_eq!(fs::read(directory.join("notes.txt~")).unwrap(), b"second");
+        assert_eq!(entries(&directory), ["notes.txt", "notes.txt~"]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_permissions_and_writes_through_links() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let directory = scratch_directory("permissions");
+        let path = directory.join("script.sh");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+        let link = directory.join("link.sh");
+        symlink(&path, &link).unwrap();
+
+        write(&link.to_string_lossy(), b"new", false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o751);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn failures_leave_the_original_alone() {
+        let directory = scratch_directory("failure");
+        // A directory can't be replaced by a file, so the last step fails
+        let path = directory.join("occupied");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("inside.txt"), b"kept").unwrap();
+
+        let err = write(&path.to_string_lossy(), b"new", false).unwrap_err();
+        assert!(err.to_string().starts_with("Could not replace the file"));
+        assert_eq!(fs::read(path.join("inside.txt")).unwrap(), b"kept");
+        assert_eq!(entries(&directory), ["occupied"]);
+
+        let missing = directory.join("missing").join("file.txt");
+        assert!(write(&missing.to_string_lossy(), b"new", false).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
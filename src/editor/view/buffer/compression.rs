@@ -0,0 +1,118 @@
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::{
+    io::{Error, ErrorKind, Read, Write},
+    path::Path,
+};
+use xz2::{read::XzDecoder, write::XzEncoder};
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\0";
+// The presets the command line tools use by default
+const ZSTD_LEVEL: i32 = 3;
+const XZ_PRESET: u32 = 6;
+
+// Formats compressed files are read in, and written back in when saving
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    // Format the contents of a file are compressed in, going by the bytes they start with
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else if bytes.starts_with(XZ_MAGIC) {
+            Some(Self::Xz)
+        } else {
+            None
+        }
+    }
+
+    // Format a file should be written in going by its name, for files not read from disk
+    pub fn for_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "gz" => Some(Self::Gzip),
+            "zst" => Some(Self::Zstd),
+            "xz" => Some(Self::Xz),
+            _ => None,
+        }
+    }
+
+    // Name of the format as its file extension, as shown in the status bar
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+            Self::Xz => "xz",
+        }
+    }
+
+    // Concatenated streams (as `cat a.gz b.gz` makes) are read as one
+    pub fn decompress(self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut decompressed = Vec::new();
+        let result = match self {
+            Self::Gzip => MultiGzDecoder::new(bytes).read_to_end(&mut decompressed),
+            Self::Zstd => zstd::Decoder::new(bytes)
+                .and_then(|mut decoder| decoder.read_to_end(&mut decompressed)),
+            Self::Xz => XzDecoder::new_multi_decoder(bytes).read_to_end(&mut decompressed),
+        };
+        result.map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("the file is not valid {}: {err}", self.name()),
+            )
+        })?;
+        Ok(decompressed)
+    }
+
+    pub fn compress(self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Self::Zstd => zstd::encode_all(bytes, ZSTD_LEVEL),
+            Self::Xz => {
+                let mut encoder = XzEncoder::new(Vec::new(), XZ_PRESET);
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn compressed_contents_come_back_as_they_were() {
+        let text = b"rotated log line\n".repeat(100);
+        for compression in [Compression::Gzip, Compression::Zstd, Compression::Xz] {
+            let compressed = compression.compress(&text).unwrap();
+            assert!(compressed.len() < text.len());
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+            assert_eq!(compression.decompress(&compressed).unwrap(), text);
+        }
+
+        // Streams one after another, as appending to a compressed log makes
+        let mut twice = Compression::Gzip.compress(b"one\n").unwrap();
+        twice.extend(Compression::Gzip.compress(b"two\n").unwrap());
+        assert_eq!(Compression::Gzip.decompress(&twice).unwrap(), b"one\ntwo\n");
+
+        assert_eq!(Compression::detect(b"plain text"), None);
+        assert!(Compression::Xz.decompress(b"\xfd7zXZ\0 cut off").is_err());
+        assert_eq!(
+            Compression::for_path("logs/app.log.zst"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::for_path("notes.txt"), None);
+    }
+}
@@ -0,0 +1,113 @@
+// Changed lines are only matched up one by one when there are at most this many pairs
+// of them to compare, otherwise the diff lists all of them as removed and added
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+// Lines removed from `old` (`- `) and added in `new` (`+ `), each run of changes headed by
+// the line it starts at in `old`
+pub fn lines(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let common_start = old
+        .iter()
+        .zip(&new)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let common_end = old[common_start..]
+        .iter()
+        .rev()
+        .zip(new[common_start..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let old_changed = &old[common_start..old.len().saturating_sub(common_end)];
+    let new_changed = &new[common_start..new.len().saturating_sub(common_end)];
+
+    let mut output = Vec::new();
+    let mut in_change = false;
+    let mut old_row = common_start;
+    for (kind, line) in changes(old_changed, new_changed) {
+        if kind == ' ' {
+            in_change = false;
+        } else if !in_change {
+            in_change = true;
+            output.push(format!("@@ line {} @@", old_row.saturating_add(1)));
+        }
+        if kind != '+' {
+            old_row = old_row.saturating_add(1);
+        }
+        if kind != ' ' {
+            output.push(format!("{kind} {line}"));
+        }
+    }
+    output.join("\n")
+}
+
+// Every line of both sides, marked as kept (` `), removed (`-`) or added (`+`), following the
+// longest run of lines the two have in common
+fn changes<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        let removed = old.iter().map(|line| ('-', *line));
+        return removed.chain(new.iter().map(|line| ('+', *line))).collect();
+    }
+
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let width = new.len().saturating_add(1);
+    let mut common = vec![0_usize; old.len().saturating_add(1).saturating_mul(width)];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            let here = i.saturating_mul(width).saturating_add(j);
+            let below = here.saturating_add(width);
+            common[here] = if old[i] == new[j] {
+                common[below.saturating_add(1)].saturating_add(1)
+            } else {
+                common[below].max(common[here.saturating_add(1)])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let here = i.saturating_mul(width).saturating_add(j);
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push((' ', old[i]));
+            (i, j) = (i.saturating_add(1), j.saturating_add(1));
+        } else if i < old.len()
+            && (j == new.len()
+                || common[here.saturating_add(width)] >= common[here.saturating_add(1)])
+        {
+            result.push(('-', old[i]));
+            i = i.saturating_add(1);
+        } else {
+            result.push(('+', new[j]));
+            j = j.saturating_add(1);
+        }
+    }
+    result
+}
+
+// Binary files have no lines, so only the number of differing bytes is told
+pub fn bytes(old: &[u8], new: &[u8]) -> String {
+    let changed = old.iter().zip(new).filter(|(old, new)| old != new).count();
+    let resized = old.len().abs_diff(new.len());
+    format!(
+        "{} of {} bytes differ",
+        changed.saturating_add(resized),
+        old.len().max(new.len())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lines;
+
+    #[test]
+    fn lists_the_changed_lines() {
+        let old = "one\ntwo\nthree\nfour\nfive\n";
+        let new = "one\n2\nthree\nfour\nfive\nsix\n";
+        assert_eq!(
+            lines(old, new),
+            "@@ line 2 @@\n- two\n+ 2\n@@ line 6 @@\n+ six"
+        );
+        assert_eq!(lines(old, old), "");
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    time::SystemTime,
+};
+
+// What a file on disk looked like when it was last read or written, to notice when another
+// program changes it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl DiskState {
+    // State of a file that was just read or written with the given contents
+    pub fn new(path: &str, contents: &[u8]) -> Self {
+        Self::with_hash(path, hash(contents))
+    }
+
+    // Same, for contents too large to hash at once, see `ContentHasher`
+    pub fn with_hash(path: &str, hash: u64) -> Self {
+        let metadata = fs::metadata(path).ok();
+        Self {
+            modified: metadata
+                .as_ref()
+                .and_then(|metadata| metadata.modified().ok()),
+            len: metadata.map_or(0, |metadata| metadata.len()),
+            hash,
+        }
+    }
+
+    pub fn read(path: &str) -> Option<Self> {
+        let contents = fs::read(path).ok()?;
+        Some(Self::new(path, &contents))
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    // Whether the contents of the file changed since. A file that is gone (or unreadable)
+    // has nothing to reload, and saving puts it back.
+    pub fn has_changed(&mut self, path: &str) -> bool {
+        let Ok(metadata) = fs::metadata(Path::new(path)) else {
+            return false;
+        };
+        let modified = metadata.modified().ok();
+        if (modified, metadata.len()) == (self.modified, self.len) {
+            return false;
+        }
+
+        // The time also changes when a file is written with the same contents (e.g. touched)
+        let Ok(contents) = fs::read(path) else {
+            return false;
+        };
+        if hash(&contents) == self.hash {
+            self.modified = modified;
+            return false;
+        }
+        true
+    }
+}
+
+fn hash(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Hashes contents a block at a time, coming up with the same hash as hashing all of them at once
+pub struct ContentHasher(DefaultHasher);
+
+impl ContentHasher {
+    // The length goes first, as it does when a slice is hashed
+    pub fn new(len: usize) -> Self {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_usize(len);
+        Self(hasher)
+    }
+
+    pub fn write(&mut self, block: &[u8]) {
+        self.0.write(block);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash, ContentHasher};
+
+    #[test]
+    fn hashes_blocks_like_the_whole() {
+        let contents = b"first line\nsecond line\n";
+        let mut hasher = ContentHasher::new(contents.len());
+        for block in contents.chunks(5) {
+            hasher.write(block);
+        }
+        assert_eq!(hasher.finish(), hash(contents));
+    }
+}
@@ -0,0 +1,252 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+// How much of a file is looked at when guessing whether it is UTF-16 without a BOM,
+// or binary rather than text
+const SAMPLE_LENGTH: usize = 4096;
+
+// Text read from a file, along with what is needed to write it back the same way
+pub struct Decoded {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub has_bom: bool,
+    // Whether some bytes were not valid in the encoding, and were replaced by U+FFFD
+    pub had_errors: bool,
+}
+
+// A character the encoding has no bytes for, and the byte offset of it in the text
+#[derive(Debug, PartialEq, Eq)]
+pub struct Unencodable {
+    pub offset: usize,
+    pub character: char,
+}
+
+// Decode a file, either in the given encoding or in the one it looks like it is in
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> Decoded {
+    let bom = Encoding::for_bom(bytes);
+    let (encoding, bom_length) = match (encoding, bom) {
+        (Some(encoding), Some((bom_encoding, length))) if bom_encoding == encoding => {
+            (encoding, length)
+        }
+        (Some(encoding), _) => (encoding, 0),
+        (None, Some((bom_encoding, length))) => (bom_encoding, length),
+        (None, None) => (detect(bytes), 0),
+    };
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+    Decoded {
+        text: text.into_owned(),
+        encoding,
+        has_bom: bom_length > 0,
+        had_errors,
+    }
+}
+
+// Encoding of a file too large to be decoded as a whole, guessed from its start, along with
+// the length of its byte order mark
+pub fn sniff(sample: &[u8]) -> (&'static Encoding, usize) {
+    if let Some(bom) = Encoding::for_bom(sample) {
+        return bom;
+    }
+    // The sample may end in the middle of a character
+    let end = match std::str::from_utf8(sample) {
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        _ => sample.len(),
+    };
+    (detect(&sample[..end]), 0)
+}
+
+fn detect(bytes: &[u8]) -> &'static Encoding {
+    // Zero bytes are valid UTF-8 as well, so UTF-16 has to be ruled out first
+    if let Some(encoding) = detect_utf_16(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+// Text in UTF-16 without a BOM is mostly ASCII with a zero byte next to every character,
+// which is the high byte of each code unit: the second byte for little endian, the first one
+// for big endian
+fn detect_utf_16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(SAMPLE_LENGTH)];
+    let units = sample.chunks_exact(2).len();
+    if units == 0 || !sample.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let zeros_at = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even_zeros, odd_zeros) = (zeros_at(0), zeros_at(1));
+    // At least half of the code units have to look like ASCII, with no zeros on the other side
+    if odd_zeros.saturating_mul(2) >= units && even_zeros == 0 {
+        Some(UTF_16LE)
+    } else if even_zeros.saturating_mul(2) >= units && odd_zeros == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+// Whether a file looks like binary data rather than text in some encoding: either it has zero
+// bytes and isn't valid UTF-8 (text with a stray NUL is still text), or it is full of
+// control characters text doesn't use
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SAMPLE_LENGTH)];
+    if Encoding::for_bom(sample).is_some() || detect_utf_16(sample).is_some() {
+        return false;
+    }
+
+    // An error without a length is a character cut off by the end of the sample
+    let is_utf_8 =
+        std::str::from_utf8(sample).map_or_else(|err| err.error_len().is_none(), |_| true);
+    let controls = sample
+        .iter()
+        .filter(|byte| matches!(**byte, 0..=8 | 0x0e..=0x1a | 0x1c..=0x1f))
+        .count();
+    (sample.contains(&0) && !is_utf_8) || controls.saturating_mul(10) > sample.len()
+}
+
+// Encode text given in pieces, failing on the first character the encoding has no bytes for
+pub fn encode<'a>(
+    pieces: impl Iterator<Item = &'a str>,
+    encoding: &'static Encoding,
+    has_bom: bool,
+) -> Result<Vec<u8>, Unencodable> {
+    let mut bytes = Vec::new();
+    if has_bom {
+        bytes.extend_from_slice(bom(encoding));
+    }
+
+    // encoding_rs only decodes UTF-16 (its encoders write UTF-8 instead), so that is done here
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        for piece in pieces {
+            for unit in piece.encode_utf16() {
+                if encoding == UTF_16LE {
+                    bytes.extend_from_slice(&unit.to_le_bytes());
+                } else {
+                    bytes.extend_from_slice(&unit.to_be_bytes());
+                }
+            }
+        }
+        return Ok(bytes);
+    }
+
+    let mut encoder = encoding.new_encoder();
+    let mut offset: usize = 0;
+    // An empty last piece lets stateful encodings (e.g. ISO-2022-JP) switch back at the end
+    for (piece, last) in pieces.map(|piece| (piece, false)).chain([("", true)]) {
+        let mut rest = piece;
+        loop {
+            let needed = encoder
+                .max_buffer_length_from_utf8_without_replacement(rest.len())
+                .unwrap_or(rest.len());
+            bytes.reserve(needed);
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut bytes, last);
+            offset = offset.saturating_add(read);
+            rest = &rest[read..];
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(character) => {
+                    return Err(Unencodable {
+                        offset: offset.saturating_sub(character.len_utf8()),
+                        character,
+                    })
+                }
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn bom(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_8 {
+        b"\xef\xbb\xbf"
+    } else if encoding == UTF_16LE {
+        b"\xff\xfe"
+    } else if encoding == UTF_16BE {
+        b"\xfe\xff"
+    } else {
+        b""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, is_binary, Unencodable};
+    use encoding_rs::{SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    #[test]
+    fn detects_the_encoding_of_a_file() {
+        let utf_8 = decode("caf\u{e9}".as_bytes(), None);
+        assert_eq!((utf_8.text.as_str(), utf_8.encoding), ("caf\u{e9}", UTF_8));
+
+        let latin = decode(b"na\xefve caf\xe9 cr\xe8me br\xfbl\xe9e", None);
+        assert_eq!(latin.encoding, WINDOWS_1252);
+        assert_eq!(
+            latin.text,
+            "na\u{ef}ve caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e"
+        );
+
+        let bom = decode(b"\xff\xfeh\0i\0", None);
+        assert_eq!((bom.text.as_str(), bom.encoding), ("hi", UTF_16LE));
+        assert!(bom.has_bom);
+
+        let no_bom = decode(b"\0h\0i\0\n", None);
+        assert_eq!((no_bom.text.as_str(), no_bom.encoding), ("hi\n", UTF_16BE));
+        assert!(!no_bom.has_bom);
+    }
+
+    #[test]
+    fn tells_binary_data_from_text() {
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\0\0\0\xff\xfe"));
+        assert!(is_binary(b"\x01\x02\x03 abc"));
+        assert!(!is_binary("text with a stray \0 and \u{e9}".as_bytes()));
+        assert!(!is_binary(b"\xff\xfeh\0i\0"));
+        assert!(!is_binary(b"caf\xe9\n"));
+    }
+
+    #[test]
+    fn explicit_encodings_win_over_detection() {
+        let decoded = decode(b"\x93\xfa\x96\x7b", Some(SHIFT_JIS));
+        assert_eq!(decoded.text, "日本");
+        assert!(!decoded.had_errors);
+
+        let invalid = decode(b"\xff", Some(UTF_8));
+        assert_eq!(invalid.text, "\u{fffd}");
+        assert!(invalid.had_errors);
+    }
+
+    #[test]
+    fn encodes_back_or_reports_the_first_unencodable_character() {
+        let pieces = ["caf\u{e9} ", "ok"];
+        assert_eq!(
+            encode(pieces.into_iter(), WINDOWS_1252, false).unwrap(),
+            b"caf\xe9 ok"
+        );
+        assert_eq!(
+            encode(["hi"].into_iter(), UTF_16LE, true).unwrap(),
+            b"\xff\xfeh\0i\0"
+        );
+        assert_eq!(
+            encode(["ok ", "日本"].into_iter(), WINDOWS_1252, false),
+            Err(Unencodable {
+                offset: 3,
+                character: '日'
+            })
+        );
+    }
+}
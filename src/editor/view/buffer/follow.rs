@@ -0,0 +1,129 @@
+use encoding_rs::{Decoder, Encoding};
+use std::{
+    fs::{self, File, Metadata},
+    io::{Error, Read, Seek, SeekFrom},
+};
+
+// What happened to a followed file since it was last looked at
+pub enum Growth {
+    None,
+    // Text appended to it, which is left empty when it wasn't asked to be read
+    Appended(String),
+    // It was truncated, or another file took its place (e.g. as logs were rotated), so what
+    // was read is stale
+    Replaced,
+}
+
+// A file read as it grows, like `tail -f` does: how much of it is in the buffer already, and
+// the decoder for the rest, which keeps the start of a character cut off at the end of a read
+pub struct Follow {
+    offset: u64,
+    // Device and inode of the file, where the platform tells them
+    identity: (u64, u64),
+    decoder: Decoder,
+    // Whether the buffer was read-only before following made it so
+    pub was_read_only: bool,
+}
+
+impl Follow {
+    pub fn new(path: &str, offset: u64, encoding: &'static Encoding, was_read_only: bool) -> Self {
+        Self {
+            offset,
+            identity: fs::metadata(path).map_or((0, 0), |metadata| identity(&metadata)),
+            decoder: encoding.new_decoder_without_bom_handling(),
+            was_read_only,
+        }
+    }
+
+    // Look for more of the file, decoding it unless `read` is false (a large file reads the new
+    // bytes itself)
+    pub fn poll(&mut self, path: &str, read: bool) -> Result<Growth, Error> {
+        let metadata = fs::metadata(path)?;
+        let len = metadata.len();
+        if len < self.offset || identity(&metadata) != self.identity {
+            return Ok(Growth::Replaced);
+        }
+        if len == self.offset {
+            return Ok(Growth::None);
+        }
+
+        let mut text = String::new();
+        if read {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(self.offset))?;
+            let mut bytes = Vec::new();
+            file.take(len.saturating_sub(self.offset))
+                .read_to_end(&mut bytes)?;
+            text.reserve(
+                self.decoder
+                    .max_utf8_buffer_length(bytes.len())
+                    .unwrap_or(bytes.len()),
+            );
+            let _ = self.decoder.decode_to_string(&bytes, &mut text, false);
+        }
+        self.offset = len;
+        Ok(Growth::Appended(text))
+    }
+}
+
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+// Only a file getting shorter tells it was replaced here
+#[cfg(not(unix))]
+fn identity(_metadata: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Follow, Growth};
+    use encoding_rs::UTF_8;
+    use std::{env, fs, io::Write, process};
+
+    #[test]
+    fn reads_characters_split_across_appends() {
+        let path = env::temp_dir()
+            .join(format!("script-pad-{}-follow.log", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&path, b"start\n").unwrap();
+        let mut follow = Follow::new(&path, 6, UTF_8, false);
+        assert!(matches!(follow.poll(&path, true).unwrap(), Growth::None));
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"caf\xc3").unwrap();
+        let Growth::Appended(text) = follow.poll(&path, true).unwrap() else {
+            panic!("nothing appended");
+        };
+        assert_eq!(text, "caf");
+        file.write_all(b"\xa9\n").unwrap();
+        let Growth::Appended(text) = follow.poll(&path, true).unwrap() else {
+            panic!("nothing appended");
+        };
+        assert_eq!(text, "\u{e9}\n");
+
+        fs::write(&path, b"new\n").unwrap();
+        assert!(matches!(
+            follow.poll(&path, true).unwrap(),
+            Growth::Replaced
+        ));
+
+        // A rotated log is replaced by a new file, which may well be longer already
+        let mut follow = Follow::new(&path, 4, UTF_8, false);
+        let rotated = format!("{path}.1");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, b"rotated away\n").unwrap();
+        if cfg!(unix) {
+            assert!(matches!(
+                follow.poll(&path, true).unwrap(),
+                Growth::Replaced
+            ));
+        }
+        fs::remove_file(rotated).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+// One change to the contents of a buffer, which is all it takes to undo or redo it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    // The chars `removed` at char index `at` were replaced by `inserted`
+    Text {
+        at: usize,
+        removed: String,
+        inserted: String,
+    },
+    // A byte of a binary file was overwritten
+    Byte {
+        offset: usize,
+        old: u8,
+        new: u8,
+    },
+}
+
+impl Change {
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Text {
+                at,
+                removed,
+                inserted,
+            } => Self::Text {
+                at: *at,
+                removed: inserted.clone(),
+                inserted: removed.clone(),
+            },
+            Self::Byte { offset, old, new } => Self::Byte {
+                offset: *offset,
+                old: *new,
+                new: *old,
+            },
+        }
+    }
+
+    // Where the caret goes once the change is made: after the inserted text, or on the byte
+    pub fn end(&self) -> usize {
+        match self {
+            Self::Text { at, inserted, .. } => at.saturating_add(inserted.chars().count()),
+            Self::Byte { offset, .. } => *offset,
+        }
+    }
+}
+
+// Undo and redo stacks of a buffer. The changes made by one command form a step, which is
+// undone as a whole; so do runs of typed chars, up to the next space or other command.
+#[derive(Debug)]
+pub struct History {
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    // Whether changes go into the last step instead of starting a new one
+    open: bool,
+    typing: bool,
+    // Number of steps done when the buffer matched its file, if that state is still reachable
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            open: false,
+            typing: false,
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    // Called before each command, which makes its changes a step of their own unless it goes on
+    // typing a word
+    pub fn begin_step(&mut self, typing: bool) {
+        if !(typing && self.typing) {
+            self.open = false;
+        }
+        self.typing = typing;
+    }
+
+    pub fn record(&mut self, change: Change) {
+        if !self.redo.is_empty() {
+            self.redo.clear();
+            if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+                self.saved = None;
+            }
+        }
+        match self.undo.last_mut() {
+            Some(step) if self.open => step.push(change),
+            _ => {
+                self.undo.push(vec![change]);
+                self.open = true;
+            }
+        }
+    }
+
+    // The changes to make to undo the last step, last change first
+    pub fn undo(&mut self) -> Option<Vec<Change>> {
+        self.open = false;
+        let step = self.undo.pop()?;
+        let inverse = step.iter().rev().map(Change::inverse).collect();
+        self.redo.push(step);
+        Some(inverse)
+    }
+
+    pub fn redo(&mut self) -> Option<Vec<Change>> {
+        self.open = false;
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        Some(step)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.open = false;
+        self.saved = Some(self.undo.len());
+    }
+
+    // The file matches none of the states in the history, e.g. once a swap file was recovered
+    pub fn forget_saved(&mut self) {
+        self.saved = None;
+    }
+
+    // Whether undoing or redoing got back to the contents of the file
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, History};
+
+    fn typed(at: usize, text: &str) -> Change {
+        Change::Text {
+            at,
+            removed: String::new(),
+            inserted: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn steps_are_undone_and_redone_as_a_whole() {
+        let mut history = History::default();
+        for (at, char) in ["a", "b"].iter().enumerate() {
+            history.begin_step(true);
+            history.record(typed(at, char));
+        }
+        history.begin_step(false);
+        history.record(typed(2, "\n"));
+        assert!(!history.is_saved());
+
+        assert_eq!(history.undo(), Some(vec![typed(2, "\n").inverse()]));
+        assert_eq!(
+            history.undo(),
+            Some(vec![typed(1, "b").inverse(), typed(0, "a").inverse()])
+        );
+        assert!(history.is_saved());
+        assert_eq!(history.undo(), None);
+
+        assert_eq!(history.redo(), Some(vec![typed(0, "a"), typed(1, "b")]));
+        // A new change drops what could be redone, and with it the saved state
+        history.mark_saved();
+        history.undo();
+        history.begin_step(false);
+        history.record(typed(0, "c"));
+        assert_eq!(history.redo(), None);
+        history.undo();
+        assert!(!history.is_saved());
+    }
+}
@@ -0,0 +1,512 @@
+use super::{compression::Compression, disk_state::ContentHasher, encoding};
+use encoding_rs::{CoderResult, Encoding};
+use ropey::{Rope, RopeBuilder};
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{Error, ErrorKind},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// Files at least this large are opened in large-file mode, unless told otherwise
+const DEFAULT_THRESHOLD: u64 = 64 * 1024 * 1024;
+// Where lines start is remembered for every this many lines; the lines in between are found
+// by scanning from there, which keeps the index small even for billions of lines
+const CHECKPOINT_STRIDE: usize = 1024;
+// How many lines are indexed between letting the editor know how far it got
+const PUBLISH_STRIDE: usize = 64 * 1024;
+// How much of the file is looked at to tell its encoding, and whether it is text at all
+const SAMPLE_LENGTH: usize = 64 * 1024;
+// How much is read at a time when looking for a line, and when indexing
+const BLOCK_LENGTH: usize = 64 * 1024;
+const INDEX_BLOCK_LENGTH: usize = 1024 * 1024;
+
+thread_local! {
+    static THRESHOLD: Cell<u64> = const { Cell::new(DEFAULT_THRESHOLD) };
+}
+
+pub fn threshold() -> u64 {
+    THRESHOLD.get()
+}
+
+pub fn set_threshold(bytes: u64) {
+    THRESHOLD.set(bytes);
+}
+
+// Line starts found by the indexing thread so far
+#[derive(Default)]
+struct LineIndex {
+    // Offset where line 0, CHECKPOINT_STRIDE, 2 * CHECKPOINT_STRIDE, ... start
+    checkpoints: Mutex<Vec<usize>>,
+    // Lines whose start is known, and bytes looked through to find them
+    lines: AtomicUsize,
+    scanned: AtomicUsize,
+    done: AtomicBool,
+    // The file was closed before indexing (or reading it in whole) finished
+    cancelled: AtomicBool,
+}
+
+// All of the file, read in by a background thread so it can be edited
+pub struct WholeText {
+    pub text: Rope,
+    // Whether some bytes were not valid in the encoding, and were replaced by U+FFFD
+    pub had_errors: bool,
+    // Of the bytes, to tell changes by other programs apart, see `DiskState::with_hash`
+    pub hash: u64,
+}
+
+// A file too large to be read into memory as a whole. Lines are only read (and decoded) when
+// they are looked at, while a background thread finds where each one starts. Only encodings
+// where a line break is a `\n` byte on its own can be read this way.
+//
+// The file isn't mapped into memory: a followed log may be truncated at any time, which would
+// make reading the mapped part that is gone crash the editor, while a read just comes up short.
+pub struct LargeFile {
+    file: Arc<File>,
+    // Bytes of the file the buffer shows; more may have been appended since, see `extend`
+    len: usize,
+    pub encoding: &'static Encoding,
+    // Start of the text, after the byte order mark if there is one
+    start: usize,
+    has_crlf: bool,
+    has_final_newline: bool,
+    index: Arc<LineIndex>,
+    // Reading the whole file, once that was started
+    reading: Option<Receiver<Result<WholeText, Error>>>,
+}
+
+impl LargeFile {
+    // Open the file and start indexing it. Returns `None` for files that have to be read as a
+    // whole instead: binary ones (shown in the hex view), compressed ones, UTF-16 text and empty
+    // files.
+    pub fn open(path: &str) -> Result<Option<Self>, Error> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len()).unwrap_or(usize::MAX);
+        if len == 0 {
+            return Ok(None);
+        }
+        let mut sample = vec![0; len.min(SAMPLE_LENGTH)];
+        let read = read_at(&file, 0, &mut sample)?;
+        sample.truncate(read);
+        let (encoding, start) = encoding::sniff(&sample);
+        if !encoding.is_ascii_compatible()
+            || encoding::is_binary(&sample)
+            || Compression::detect(&sample).is_some()
+        {
+            return Ok(None);
+        }
+        // Only the first line break decides, if it is in the sample at all
+        let text = sample.get(start..).unwrap_or_default();
+        let has_crlf = memchr::memchr(b'\n', text)
+            .and_then(|length| length.checked_sub(1))
+            .is_some_and(|length| text.get(length) == Some(&b'\r'));
+
+        let mut large_file = Self {
+            file: Arc::new(file),
+            len,
+            encoding,
+            start,
+            has_crlf,
+            has_final_newline: false,
+            index: Arc::new(LineIndex {
+                checkpoints: Mutex::new(vec![start]),
+                lines: AtomicUsize::new(1),
+                ..LineIndex::default()
+            }),
+            reading: None,
+        };
+        large_file.has_final_newline = large_file.last_byte() == Some(b'\n');
+        large_file.index_from(start);
+        Ok(Some(large_file))
+    }
+
+    // Take in what was appended to the file, and go on indexing where the old end was. Only to
+    // be called once indexing is done, so the index isn't added to twice.
+    pub fn extend(&mut self) -> Result<(), Error> {
+        let len = usize::try_from(self.file.metadata()?.len()).unwrap_or(usize::MAX);
+        if len <= self.len {
+            return Ok(());
+        }
+        let old_len = self.len;
+        self.len = len;
+        self.has_final_newline = self.last_byte() == Some(b'\n');
+        self.index.done.store(false, Ordering::Release);
+        self.index_from(old_len);
+        Ok(())
+    }
+
+    fn index_from(&self, from: usize) {
+        let (file, index, len) = (Arc::clone(&self.file), Arc::clone(&self.index), self.len);
+        thread::spawn(move || index_lines(&file, from, len, &index));
+    }
+
+    // Lines known so far; this grows until indexing is done
+    pub fn height(&self) -> usize {
+        self.index.lines.load(Ordering::Acquire)
+    }
+
+    // How far indexing got, in percent, while it is still going on
+    pub fn indexing_progress(&self) -> Option<usize> {
+        if self.index.done.load(Ordering::Acquire) {
+            return None;
+        }
+        let scanned = self.index.scanned.load(Ordering::Acquire);
+        Some(
+            scanned
+                .saturating_mul(100)
+                .checked_div(self.len)
+                .unwrap_or(0),
+        )
+    }
+
+    // Text of a line, without its line break
+    pub fn line(&self, row: usize) -> Option<String> {
+        if row >= self.height() {
+            return None;
+        }
+        let checkpoint = row.checked_div(CHECKPOINT_STRIDE)?;
+        let checkpoint = *self.index.checkpoints.lock().ok()?.get(checkpoint)?;
+        let mut offset = self.skip_lines(checkpoint, row.checked_rem(CHECKPOINT_STRIDE)?)?;
+
+        let mut line = Vec::new();
+        let mut block = vec![0; BLOCK_LENGTH];
+        loop {
+            let read = self.read_block(offset, &mut block);
+            if let Some(length) = memchr::memchr(b'\n', &block[..read]) {
+                line.extend_from_slice(&block[..length]);
+                break;
+            }
+            line.extend_from_slice(&block[..read]);
+            if read < block.len() {
+                break;
+            }
+            offset = offset.saturating_add(read);
+        }
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        let (text, _) = self.encoding.decode_without_bom_handling(line);
+        Some(text.into_owned())
+    }
+
+    // Start of the line `count` lines after the one starting at `from`
+    fn skip_lines(&self, from: usize, count: usize) -> Option<usize> {
+        let (mut offset, mut remaining) = (from, count);
+        let mut block = vec![0; BLOCK_LENGTH];
+        while remaining > 0 {
+            let read = self.read_block(offset, &mut block);
+            if read == 0 {
+                return None;
+            }
+            for length in memchr::memchr_iter(b'\n', &block[..read]) {
+                remaining = remaining.saturating_sub(1);
+                if remaining == 0 {
+                    return Some(offset.saturating_add(length).saturating_add(1));
+                }
+            }
+            offset = offset.saturating_add(read);
+        }
+        Some(from)
+    }
+
+    // Read the file from an offset into `block`, up to the end the buffer knows of. Returns how
+    // much was read, which is less than that if the file got shorter meanwhile.
+    fn read_block(&self, offset: usize, block: &mut [u8]) -> usize {
+        let length = self.len.saturating_sub(offset).min(block.len());
+        read_at(&self.file, offset, &mut block[..length]).unwrap_or(0)
+    }
+
+    fn last_byte(&self) -> Option<u8> {
+        let mut byte = [0];
+        (self.read_block(self.len.saturating_sub(1), &mut byte) == 1).then_some(byte[0])
+    }
+
+    pub fn has_bom(&self) -> bool {
+        self.start > 0
+    }
+
+    pub fn is_text_empty(&self) -> bool {
+        self.len <= self.start
+    }
+
+    pub fn has_final_newline(&self) -> bool {
+        !self.is_text_empty() && self.has_final_newline
+    }
+
+    // Whether the first line break is a CRLF, which new lines would use as well
+    pub fn has_crlf(&self) -> bool {
+        self.has_crlf
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // Read, decode and hash all of the file on a thread of its own, see `take_text`
+    pub fn start_reading(&mut self) {
+        if self.reading.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let (file, index) = (Arc::clone(&self.file), Arc::clone(&self.index));
+        let (start, len, encoding) = (self.start, self.len, self.encoding);
+        thread::spawn(move || {
+            let _ = sender.send(read_text(&file, start, len, encoding, &index));
+        });
+        self.reading = Some(receiver);
+    }
+
+    pub fn is_reading(&self) -> bool {
+        self.reading.is_some()
+    }
+
+    // The whole text once reading it is done. A failed read can be started again.
+    pub fn take_text(&mut self) -> Option<Result<WholeText, Error>> {
+        let result = match self.reading.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(Error::other("Reading the file stopped.")),
+        };
+        self.reading = None;
+        Some(result)
+    }
+
+    // All of the file, e.g. to save it
+    pub fn read_all(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0; self.len];
+        if read_at(&self.file, 0, &mut bytes)? < self.len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The file got shorter while it was read.",
+            ));
+        }
+        Ok(bytes)
+    }
+}
+
+impl Drop for LargeFile {
+    fn drop(&mut self) {
+        self.index.cancelled.store(true, Ordering::Release);
+    }
+}
+
+// Fill as much of `buf` as the file has from `offset` on. Reads at a position leave the file
+// offset alone, so the indexing thread and the editor can share the file.
+fn read_at(file: &File, offset: usize, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut read: usize = 0;
+    while read < buf.len() {
+        let position = u64::try_from(offset.saturating_add(read)).unwrap_or(u64::MAX);
+        match read_some_at(file, position, &mut buf[read..]) {
+            Ok(0) => break,
+            Ok(length) => read = read.saturating_add(length),
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(unix)]
+fn read_some_at(file: &File, position: u64, buf: &mut [u8]) -> Result<usize, Error> {
+    std::os::unix::fs::FileExt::read_at(file, buf, position)
+}
+
+#[cfg(windows)]
+fn read_some_at(file: &File, position: u64, buf: &mut [u8]) -> Result<usize, Error> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, position)
+}
+
+// Runs on its own thread, a block at a time, so the bytes are never in memory as a whole
+fn read_text(
+    file: &File,
+    start: usize,
+    len: usize,
+    encoding: &'static Encoding,
+    index: &LineIndex,
+) -> Result<WholeText, Error> {
+    let mut hasher = ContentHasher::new(len);
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut builder = RopeBuilder::new();
+    let mut had_errors = false;
+    let mut block = vec![0; INDEX_BLOCK_LENGTH];
+    let mut chunk = String::new();
+    let mut offset = 0;
+    while offset < len {
+        if index.cancelled.load(Ordering::Acquire) {
+            return Err(Error::new(ErrorKind::Interrupted, "The file was closed."));
+        }
+        let length = len.saturating_sub(offset).min(block.len());
+        if read_at(file, offset, &mut block[..length])? < length {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The file got shorter while it was read.",
+            ));
+        }
+        hasher.write(&block[..length]);
+        // The byte order mark is left out of the text
+        let mut bytes = block
+            .get(start.saturating_sub(offset)..length)
+            .unwrap_or_default();
+        offset = offset.saturating_add(length);
+        chunk.clear();
+        loop {
+            chunk.reserve(
+                decoder
+                    .max_utf8_buffer_length(bytes.len())
+                    .unwrap_or(BLOCK_LENGTH),
+            );
+            let (result, read, errors) = decoder.decode_to_string(bytes, &mut chunk, offset >= len);
+            had_errors |= errors;
+            bytes = bytes.get(read..).unwrap_or_default();
+            if result == CoderResult::InputEmpty {
+                break;
+            }
+        }
+        builder.append(&chunk);
+    }
+    Ok(WholeText {
+        text: builder.finish(),
+        had_errors,
+        hash: hasher.finish(),
+    })
+}
+
+// Runs on its own thread, going on from the lines found so far and letting the editor know how
+// far it got every so often
+fn index_lines(file: &File, from: usize, len: usize, index: &LineIndex) {
+    let publish = |lines: usize, scanned: usize, checkpoints: &mut Vec<usize>| {
+        if let Ok(mut published) = index.checkpoints.lock() {
+            published.append(checkpoints);
+        }
+        // Only once the lines can be looked up through the checkpoints
+        index.scanned.store(scanned, Ordering::Release);
+        index.lines.store(lines, Ordering::Release);
+    };
+
+    let mut lines = index.lines.load(Ordering::Acquire);
+    let mut checkpoints = Vec::new();
+    let mut block = vec![0; INDEX_BLOCK_LENGTH];
+    let mut offset = from;
+    while offset < len {
+        if index.cancelled.load(Ordering::Acquire) {
+            return;
+        }
+        let length = len.saturating_sub(offset).min(block.len());
+        let read = read_at(file, offset, &mut block[..length]).unwrap_or(0);
+        if read == 0 {
+            break;
+        }
+        for length in memchr::memchr_iter(b'\n', &block[..read]) {
+            let line_break = offset.saturating_add(length);
+            if lines.is_multiple_of(CHECKPOINT_STRIDE) {
+                checkpoints.push(line_break.saturating_add(1));
+            }
+            lines = lines.saturating_add(1);
+            if lines.is_multiple_of(PUBLISH_STRIDE) {
+                publish(lines, line_break, &mut checkpoints);
+            }
+        }
+        offset = offset.saturating_add(read);
+    }
+    publish(lines, len, &mut checkpoints);
+    index.done.store(true, Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LargeFile;
+    use std::{env, fmt::Write as _, fs, io::Write, process, thread, time::Duration};
+
+    fn open(name: &str, contents: &[u8]) -> (String, LargeFile) {
+        let path = env::temp_dir()
+            .join(format!("script-pad-{}-{name}", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&path, contents).unwrap();
+        let large_file = LargeFile::open(&path).unwrap().unwrap();
+        while large_file.indexing_progress().is_some() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        (path, large_file)
+    }
+
+    #[test]
+    fn finds_lines_across_checkpoints() {
+        let mut contents = String::new();
+        for row in 0..3000 {
+            writeln!(contents, "line {row}").unwrap();
+        }
+        let (path, large_file) = open("large.log", contents.as_bytes());
+
+        // The text ends with a line break, after which there is an empty last line
+        assert_eq!(large_file.height(), 3001);
+        assert_eq!(large_file.line(0).as_deref(), Some("line 0"));
+        assert_eq!(large_file.line(1024).as_deref(), Some("line 1024"));
+        assert_eq!(large_file.line(2999).as_deref(), Some("line 2999"));
+        assert_eq!(large_file.line(3000).as_deref(), Some(""));
+        assert_eq!(large_file.line(3001), None);
+        assert!(large_file.has_final_newline());
+        assert!(!large_file.has_crlf());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn goes_on_indexing_after_the_file_grew() {
+        let (path, mut large_file) = open("large-growing.log", b"one\ntw");
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"o\nthree\n").unwrap();
+        large_file.extend().unwrap();
+        while large_file.indexing_progress().is_some() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(large_file.height(), 4);
+        assert_eq!(large_file.line(1).as_deref(), Some("two"));
+        assert_eq!(large_file.line(2).as_deref(), Some("three"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn lines_of_a_truncated_file_come_back_empty() {
+        let (path, large_file) = open("large-truncated.log", b"one\ntwo\nthree\n");
+        fs::write(&path, b"").unwrap();
+        assert_eq!(large_file.line(0).as_deref(), Some(""));
+        assert_eq!(large_file.line(2), None);
+        assert!(large_file.read_all().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_the_whole_text_in_the_background() {
+        let (path, mut large_file) = open("large-whole.log", b"\xef\xbb\xbfcaf\xc3\xa9\n\xff");
+        large_file.start_reading();
+        let whole = loop {
+            match large_file.take_text() {
+                Some(whole) => break whole.unwrap(),
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        };
+        // Without the byte order mark, and with the invalid byte replaced
+        assert_eq!(whole.text.to_string(), "caf\u{e9}\n\u{fffd}");
+        assert!(whole.had_errors);
+        assert!(!large_file.is_reading());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn decodes_lines_in_the_encoding_of_the_file() {
+        let (path, large_file) = open("large-latin.log", b"caf\xe9 cr\xe8me\r\nna\xefve");
+        assert_eq!(large_file.line(0).as_deref(), Some("caf\u{e9} cr\u{e8}me"));
+        assert_eq!(large_file.line(1).as_deref(), Some("na\u{ef}ve"));
+        assert!(large_file.has_crlf());
+        assert!(!large_file.has_final_newline());
+        fs::remove_file(&path).unwrap();
+
+        // UTF-16 has to be read as a whole
+        fs::write(&path, b"\xff\xfeh\0i\0").unwrap();
+        assert!(LargeFile::open(&path).unwrap().is_none());
+        fs::remove_file(path).unwrap();
+    }
+}
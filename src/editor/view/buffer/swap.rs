@@ -0,0 +1,53 @@
+use super::atomic_write;
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+// First line of every swap file, so nothing else is ever mistaken for one
+const HEADER: &[u8] = b"script-pad swap\n";
+
+// Unsaved changes to `notes.txt` are kept in `.notes.txt.swp` next to it
+pub fn path_for(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.swp"))
+}
+
+pub fn write(file_path: &str, contents: &[u8]) -> Result<(), Error> {
+    let mut bytes = HEADER.to_vec();
+    bytes.extend_from_slice(contents);
+    atomic_write::write(&path_for(file_path).to_string_lossy(), &bytes, false)
+}
+
+// Contents journaled for a file, if there is a swap file for it
+pub fn read(file_path: &str) -> Option<Vec<u8>> {
+    let bytes = fs::read(path_for(file_path)).ok()?;
+    bytes.strip_prefix(HEADER).map(<[u8]>::to_vec)
+}
+
+pub fn remove(file_path: &str) -> Result<(), Error> {
+    match fs::remove_file(path_for(file_path)) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::path_for;
+    use std::path::PathBuf;
+
+    #[test]
+    fn swap_files_sit_next_to_the_document() {
+        assert_eq!(
+            path_for("docs/notes.txt"),
+            PathBuf::from("docs/.notes.txt.swp")
+        );
+        assert_eq!(path_for("notes.txt"), PathBuf::from(".notes.txt.swp"));
+    }
+}
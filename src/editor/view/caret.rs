@@ -1,55 +1,105 @@
 use super::{buffer::Buffer, line::Line, position::Position};
 use crate::editor::command::Direction;
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct Caret {
     pub position: Position,
     pub line_location: usize,
 }
 
 impl Caret {
-    pub fn move_caret(&mut self, direction: &Direction, buffer: &Buffer) {
+    // Keep the caret inside the buffer after it was edited elsewhere
+    pub fn clamp(&mut self, buffer: &mut Buffer) {
+        let row = self.position.row.min(buffer.height().saturating_sub(1));
+        self.line_location = self.line_location.min(Self::line_len(buffer, row));
+        self.position = Position {
+            row,
+            col: Self::location_width(buffer, row, self.line_location),
+        };
+    }
+
+    // Move the caret in front of the first grapheme starting at or after the given char offset
+    // into the current line, so it stays put relative to the text around an edit that merged
+    // or split graphemes
+    pub fn move_to_char_offset(&mut self, char_offset: usize, buffer: &mut Buffer) {
+        self.line_location = buffer
+            .line(self.position.row)
+            .map_or(0, |line| line.location_at_char_offset(char_offset));
+        self.clamp(buffer);
+    }
+
+    // Offset (in chars) of the caret into the current line
+    pub fn char_offset(&self, buffer: &mut Buffer) -> usize {
+        buffer
+            .line(self.position.row)
+            .map_or(0, |line| line.char_offset(self.line_location))
+    }
+
+    pub fn move_caret(&mut self, direction: &Direction, buffer: &mut Buffer) {
         let Position { mut row, mut col } = self.position;
-        let lines: &Vec<Line> = &buffer.lines;
+        let last_row = buffer.height().saturating_sub(1);
 
         // To handle diectionally related keyboard events
         match direction {
+            // Moving vertically keeps the caret on the same screen column, as far as the line allows
             Direction::Up => {
                 row = row.saturating_sub(1);
-                self.line_location = self.line_location.min(lines[row].len());
-                col = lines[row].get_nth_location(self.line_location);
+                self.line_location = Self::location_at_col(buffer, row, col);
+                col = Self::location_width(buffer, row, self.line_location);
             }
             Direction::Down => {
-                row = row.saturating_add(1).min(lines.len().saturating_sub(1));
-                self.line_location = self.line_location.min(lines[row].len());
-                col = lines[row].get_nth_location(self.line_location);
+                row = row.saturating_add(1).min(last_row);
+                self.line_location = Self::location_at_col(buffer, row, col);
+                col = Self::location_width(buffer, row, self.line_location);
             }
             Direction::Left => {
                 if self.line_location == 0 && row > 0 {
                     row = row.saturating_sub(1);
-                    col = lines[row].get_total_width();
-                    self.line_location = lines[row].len();
+                    col = buffer.line(row).map_or(0, Line::get_total_width);
+                    self.line_location = Self::line_len(buffer, row);
                 } else {
                     self.line_location = self.line_location.saturating_sub(1);
-                    col = lines[row].get_nth_location(self.line_location);
+                    col = Self::location_width(buffer, row, self.line_location);
                 }
             }
             Direction::Right => {
-                if self.line_location == lines[row].len() {
-                    row = row.saturating_add(1).min(lines.len().saturating_sub(1));
+                if self.line_location == Self::line_len(buffer, row) {
+                    row = row.saturating_add(1).min(last_row);
                     col = 0;
                     self.line_location = 0;
                 } else {
                     self.line_location = self.line_location.saturating_add(1);
-                    col = lines[row].get_nth_location(self.line_location);
+                    col = Self::location_width(buffer, row, self.line_location);
                 }
             }
             Direction::PageUp => row = 0,
-            Direction::PageDown => row = lines.len().saturating_sub(1),
-            Direction::Home => col = 0,
-            Direction::End => col = lines[row].len(),
+            Direction::PageDown => row = last_row,
+            Direction::Home => {
+                col = 0;
+                self.line_location = 0;
+            }
+            Direction::End => {
+                self.line_location = Self::line_len(buffer, row);
+                col = Self::location_width(buffer, row, self.line_location);
+            }
         }
 
         self.position = Position { row, col };
     }
+
+    fn line_len(buffer: &mut Buffer, row: usize) -> usize {
+        buffer.line(row).map_or(0, Line::len)
+    }
+
+    fn location_at_col(buffer: &mut Buffer, row: usize, col: usize) -> usize {
+        buffer
+            .line(row)
+            .map_or(0, |line| line.get_location_at_col(col))
+    }
+
+    fn location_width(buffer: &mut Buffer, row: usize, line_location: usize) -> usize {
+        buffer
+            .line(row)
+            .map_or(0, |line| line.get_nth_location(line_location))
+    }
 }
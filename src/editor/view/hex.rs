@@ -0,0 +1,273 @@
+use super::super::command::Direction;
+use super::super::terminal::Style;
+
+// Bytes shown per row at most, however wide the view is
+const MAX_BYTES_PER_ROW: usize = 16;
+
+const DIM: Style = Style {
+    reverse: false,
+    dim: true,
+    foreground: None,
+    background: None,
+};
+
+// Caret in the hex view: the byte it is on, and which of its two hex digits
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HexCaret {
+    pub offset: usize,
+    pub low_nibble: bool,
+}
+
+// How the bytes are laid out in a view of a given width. Each row shows an offset, then the
+// bytes as hex, then the same bytes as ASCII:
+// `0010  48 65 6C  Hel`
+#[derive(Clone, Copy)]
+pub struct HexLayout {
+    offset_digits: usize,
+    pub bytes_per_row: usize,
+}
+
+impl HexLayout {
+    pub fn new(len: usize, width: usize) -> Self {
+        let offset_digits = format!("{:X}", len.saturating_sub(1)).len().max(4);
+        // Each byte takes three columns as hex and one as ASCII, around the offset and two gaps
+        let bytes_per_row = width
+            .saturating_sub(offset_digits)
+            .saturating_sub(3)
+            .checked_div(4)
+            .unwrap_or(0)
+            .clamp(1, MAX_BYTES_PER_ROW);
+        Self {
+            offset_digits,
+            bytes_per_row,
+        }
+    }
+
+    pub fn height(&self, len: usize) -> usize {
+        len.div_ceil(self.bytes_per_row)
+    }
+
+    pub fn row_of(&self, offset: usize) -> usize {
+        offset.checked_div(self.bytes_per_row).unwrap_or(0)
+    }
+
+    // Screen column of the hex digit the caret is on, relative to the view
+    pub fn caret_col(&self, caret: HexCaret) -> usize {
+        let index = caret.offset.checked_rem(self.bytes_per_row).unwrap_or(0);
+        self.offset_digits
+            .saturating_add(2)
+            .saturating_add(index.saturating_mul(3))
+            .saturating_add(usize::from(caret.low_nibble))
+    }
+
+    pub fn render_row(&self, bytes: &[u8], row: usize) -> Vec<(String, Style)> {
+        let start = row.saturating_mul(self.bytes_per_row);
+        let end = start.saturating_add(self.bytes_per_row).min(bytes.len());
+        let Some(row_bytes) = bytes.get(start..end) else {
+            return Vec::new();
+        };
+
+        let digits = self.offset_digits;
+        let mut hex: String = row_bytes
+            .iter()
+            .map(|byte| format!("{byte:02X} "))
+            .collect::<Vec<String>>()
+            .concat();
+        // Keep the ASCII column lined up on a short last row
+        let padding = self.bytes_per_row.saturating_sub(row_bytes.len());
+        hex.push_str(&"   ".repeat(padding));
+
+        let mut spans = vec![
+            (format!("{start:0digits$X}  "), DIM),
+            (format!("{hex} "), Style::default()),
+        ];
+        for byte in row_bytes {
+            let span = if byte.is_ascii_graphic() || *byte == b' ' {
+                (char::from(*byte).to_string(), Style::default())
+            } else {
+                (".".to_string(), DIM)
+            };
+            spans.push(span);
+        }
+        spans
+    }
+}
+
+impl HexCaret {
+    // Left and right step through the hex digits one at a time, the other directions by byte
+    pub fn move_caret(
+        &mut self,
+        direction: &Direction,
+        layout: HexLayout,
+        len: usize,
+        page: usize,
+    ) {
+        let last = len.saturating_sub(1);
+        let row_start = layout
+            .row_of(self.offset)
+            .saturating_mul(layout.bytes_per_row);
+        let page_bytes = layout.bytes_per_row.saturating_mul(page.max(1));
+        match direction {
+            Direction::Left => {
+                if self.low_nibble {
+                    self.low_nibble = false;
+                } else if self.offset > 0 {
+                    self.offset = self.offset.saturating_sub(1);
+                    self.low_nibble = true;
+                }
+            }
+            Direction::Right => self.advance(len),
+            Direction::Up => {
+                if self.offset >= layout.bytes_per_row {
+                    self.offset = self.offset.saturating_sub(layout.bytes_per_row);
+                }
+            }
+            Direction::Down => {
+                if self.offset.saturating_add(layout.bytes_per_row) <= last {
+                    self.offset = self.offset.saturating_add(layout.bytes_per_row);
+                }
+            }
+            Direction::PageUp => self.offset = self.offset.saturating_sub(page_bytes),
+            Direction::PageDown => self.offset = self.offset.saturating_add(page_bytes).min(last),
+            Direction::Home => {
+                self.offset = row_start;
+                self.low_nibble = false;
+            }
+            Direction::End => {
+                self.offset = row_start
+                    .saturating_add(layout.bytes_per_row)
+                    .saturating_sub(1)
+                    .min(last);
+                self.low_nibble = false;
+            }
+        }
+    }
+
+    // Move on to the next hex digit, staying on the last one at the end of the bytes
+    pub fn advance(&mut self, len: usize) {
+        if !self.low_nibble {
+            self.low_nibble = true;
+        } else if self.offset.saturating_add(1) < len {
+            self.offset = self.offset.saturating_add(1);
+            self.low_nibble = false;
+        }
+    }
+}
+
+// Replace the high or low half of a byte with a hex digit
+pub fn set_nibble(byte: u8, low_nibble: bool, digit: u8) -> u8 {
+    if low_nibble {
+        (byte & 0xf0) | (digit & 0x0f)
+    } else {
+        (byte & 0x0f) | (digit << 4)
+    }
+}
+
+// A pattern is either hex digits (spaces between bytes are optional), or quoted ASCII text:
+// `DE AD be ef` or `"PNG"`
+pub fn parse_pattern(pattern: &str) -> Result<Vec<u8>, String> {
+    let pattern = pattern.trim();
+    if let Some(text) = pattern
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|text| !text.is_empty())
+    {
+        return Ok(text.as_bytes().to_vec());
+    }
+
+    let digits: Vec<u8> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).and_then(|digit| u8::try_from(digit).ok()))
+        .collect::<Option<_>>()
+        .ok_or_else(|| "Use hex digits (e.g. DE AD) or quoted text (e.g. \"PNG\").".to_string())?;
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err("Give two hex digits for every byte.".to_string());
+    }
+    Ok(digits
+        .chunks_exact(2)
+        .map(|pair| set_nibble(pair[0] << 4, true, pair[1]))
+        .collect())
+}
+
+// Offset of the next match after `from`, wrapping around to the start
+pub fn find(bytes: &[u8], pattern: &[u8], from: usize) -> Option<usize> {
+    let matches_at = |offset: &usize| {
+        bytes
+            .get(*offset..offset.saturating_add(pattern.len()))
+            .is_some_and(|window| window == pattern)
+    };
+    let start = from.saturating_add(1);
+    (start..bytes.len())
+        .chain(0..start.min(bytes.len()))
+        .find(matches_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, parse_pattern, set_nibble, HexCaret, HexLayout};
+    use crate::editor::command::Direction;
+
+    #[test]
+    fn lays_out_rows_to_fit_the_width() {
+        let layout = HexLayout::new(0x20, 80);
+        assert_eq!(layout.bytes_per_row, 16);
+        let layout = HexLayout::new(0x20, 20);
+        assert_eq!(layout.bytes_per_row, 3);
+
+        let row: Vec<String> = layout
+            .render_row(b"Hi\0\x7fA", 1)
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(row.concat(), "0003  7F 41     .A");
+        let caret = HexCaret {
+            offset: 4,
+            low_nibble: true,
+        };
+        assert_eq!(layout.caret_col(caret), 10);
+    }
+
+    #[test]
+    fn caret_steps_through_nibbles() {
+        let layout = HexLayout::new(8, 20);
+        let mut caret = HexCaret::default();
+        caret.move_caret(&Direction::Right, layout, 8, 1);
+        assert_eq!(
+            caret,
+            HexCaret {
+                offset: 0,
+                low_nibble: true
+            }
+        );
+        caret.move_caret(&Direction::Right, layout, 8, 1);
+        caret.move_caret(&Direction::Down, layout, 8, 1);
+        assert_eq!(
+            caret,
+            HexCaret {
+                offset: 4,
+                low_nibble: false
+            }
+        );
+        caret.move_caret(&Direction::End, layout, 8, 1);
+        assert_eq!(caret.offset, 5);
+        caret.move_caret(&Direction::Down, layout, 8, 1);
+        assert_eq!(caret.offset, 5);
+    }
+
+    #[test]
+    fn edits_and_searches_bytes() {
+        assert_eq!(set_nibble(0x12, false, 0xa), 0xa2);
+        assert_eq!(set_nibble(0x12, true, 0xa), 0x1a);
+
+        assert_eq!(parse_pattern("de AD"), Ok(vec![0xde, 0xad]));
+        assert_eq!(parse_pattern("\"PNG\""), Ok(b"PNG".to_vec()));
+        assert!(parse_pattern("ABC").is_err());
+        assert!(parse_pattern("xyz").is_err());
+
+        let bytes = b"ab\0ab\0";
+        assert_eq!(find(bytes, b"ab", 0), Some(3));
+        assert_eq!(find(bytes, b"ab", 3), Some(0));
+        assert_eq!(find(bytes, b"zz", 0), None);
+    }
+}
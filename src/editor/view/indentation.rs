@@ -0,0 +1,146 @@
+use std::{cell::Cell, path::Path};
+
+const DEFAULT_SPACES: usize = 4;
+const MAX_SPACES: usize = 16;
+
+// One level of indentation, as the indent commands and auto-indent insert it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indentation {
+    Tab,
+    Spaces(usize),
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self::Spaces(DEFAULT_SPACES)
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Indentation> = Cell::new(Indentation::default());
+}
+
+impl Indentation {
+    pub fn current() -> Self {
+        CURRENT.get()
+    }
+
+    pub fn set_current(indentation: Self) {
+        CURRENT.set(indentation);
+    }
+
+    // `tab`, or a number of spaces
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if spec == "tab" {
+            return Ok(Self::Tab);
+        }
+        match spec.parse() {
+            Ok(spaces) if (1..=MAX_SPACES).contains(&spaces) => Ok(Self::Spaces(spaces)),
+            _ => Err(format!(
+                "Indent must be tab or 1 to {MAX_SPACES} spaces, not {spec}"
+            )),
+        }
+    }
+
+    pub fn unit(self) -> String {
+        match self {
+            Self::Tab => "\t".to_string(),
+            Self::Spaces(spaces) => " ".repeat(spaces),
+        }
+    }
+
+    // Chars to remove from the start of a line to take away one level: a tab, or up to a
+    // level's worth of spaces, whichever the line starts with
+    pub fn outdent_len(self, line: &str) -> usize {
+        if line.starts_with('\t') {
+            return 1;
+        }
+        let spaces = match self {
+            Self::Tab => DEFAULT_SPACES,
+            Self::Spaces(spaces) => spaces,
+        };
+        line.chars()
+            .take(spaces)
+            .take_while(|char| *char == ' ')
+            .count()
+    }
+}
+
+// The spaces and tabs a line starts with
+pub fn leading_whitespace(line: &str) -> &str {
+    let text = line.trim_start_matches([' ', '\t']);
+    &line[..line.len().saturating_sub(text.len())]
+}
+
+// How a kind of file nests blocks, for indenting them as they are typed
+#[derive(Debug, Clone, Copy)]
+pub struct IndentRules {
+    // Chars ending a line that opens a block, with the char closing it again if there is one
+    blocks: &'static [(char, Option<char>)],
+}
+
+const BRACES: IndentRules = IndentRules {
+    blocks: &[('{', Some('}')), ('[', Some(']')), ('(', Some(')'))],
+};
+const COLONS: IndentRules = IndentRules {
+    blocks: &[(':', None)],
+};
+
+impl IndentRules {
+    // Rules for the file type the name says, if it is one the editor knows
+    pub fn for_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "cs" | "css" | "scss" | "less"
+            | "dart" | "go" | "java" | "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "json"
+            | "kt" | "kts" | "php" | "rs" | "scala" | "swift" | "zig" => Some(BRACES),
+            "py" | "pyi" | "yaml" | "yml" => Some(COLONS),
+            _ => None,
+        }
+    }
+
+    // The block opened by the end of the text before the caret, if any
+    pub fn opened_block(self, before: &str) -> Option<(char, Option<char>)> {
+        let last = before.trim_end().chars().last()?;
+        self.blocks
+            .iter()
+            .find(|(opener, _)| *opener == last)
+            .copied()
+    }
+
+    pub fn is_closer(self, char: char) -> bool {
+        self.blocks.iter().any(|(_, closer)| *closer == Some(char))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{leading_whitespace, IndentRules, Indentation};
+
+    #[test]
+    fn levels_of_tabs_and_spaces() {
+        assert_eq!(Indentation::parse("tab"), Ok(Indentation::Tab));
+        assert_eq!(Indentation::parse("2"), Ok(Indentation::Spaces(2)));
+        assert!(Indentation::parse("0").is_err());
+        assert_eq!(Indentation::Spaces(2).unit(), "  ");
+
+        let four = Indentation::Spaces(4);
+        assert_eq!(four.outdent_len("      x"), 4);
+        assert_eq!(four.outdent_len("  x"), 2);
+        assert_eq!(four.outdent_len("\t  x"), 1);
+        assert_eq!(four.outdent_len("x"), 0);
+        assert_eq!(leading_whitespace("\t  let x"), "\t  ");
+    }
+
+    #[test]
+    fn blocks_depend_on_the_file_type() {
+        let rust = IndentRules::for_path("src/main.rs").unwrap();
+        assert_eq!(rust.opened_block("fn main() { "), Some(('{', Some('}'))));
+        assert_eq!(rust.opened_block("let x = 1;"), None);
+        assert!(rust.is_closer(']'));
+
+        let python = IndentRules::for_path("setup.py").unwrap();
+        assert_eq!(python.opened_block("if x:"), Some((':', None)));
+        assert!(!python.is_closer('}'));
+        assert!(IndentRules::for_path("notes.txt").is_none());
+    }
+}
@@ -1,23 +1,93 @@
-use std::ops::Range;
+use crate::editor::terminal::grapheme_width;
+use std::{
+    fmt::{self, Display},
+    ops::Range,
+};
+pub use text_grapheme::GraphemeKind;
 use text_grapheme::TextGrapheme;
 
 use unicode_segmentation::UnicodeSegmentation;
 
 mod text_grapheme;
+
+// A run of neighboring graphemes that are shown the same way
+#[derive(Debug, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub kind: GraphemeKind,
+    // Whether the graphemes are part of the whitespace ending the line
+    pub trailing: bool,
+}
+
+// Besides the graphemes, a line caches the cumulative widths (and char counts) in front of
+// every grapheme, so column lookups don't need to walk the whole line on every caret move.
+// Both caches have one more entry than there are graphemes, the last one being the total.
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Line {
-    pub line_content: Vec<TextGrapheme>,
+    line_content: Vec<TextGrapheme>,
+    prefix_widths: Vec<usize>,
+    prefix_chars: Vec<usize>,
+}
+
+impl Display for Line {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for grapheme in &self.line_content {
+            formatter.write_str(&grapheme.grapheme)?;
+        }
+        Ok(())
+    }
 }
 
 impl Line {
     pub fn from(content: &str) -> Self {
-        let mut content_graphemes: Vec<TextGrapheme> = Vec::new();
-        for grapheme in content.graphemes(true) {
-            content_graphemes.push(TextGrapheme::new(grapheme));
+        let mut line = Self {
+            line_content: Self::segment(content),
+            prefix_widths: vec![0],
+            prefix_chars: vec![0],
+        };
+        line.update_prefixes_from(0);
+        line
+    }
+
+    fn segment(content: &str) -> Vec<TextGrapheme> {
+        content.graphemes(true).map(TextGrapheme::new).collect()
+    }
+
+    // Replace the graphemes in `start..end` by the segmentation of `text`. Since an edit can
+    // change how the text after it clusters (e.g. a base character typed in front of a
+    // combining mark), the following graphemes are taken in as well until the boundaries
+    // line up again, so the line always matches `str::graphemes` of its content.
+    fn resegment(&mut self, start: usize, mut end: usize, text: &str) {
+        let mut text = text.to_string();
+        let mut graphemes = Self::segment(&text);
+
+        while let (Some(last), Some(next)) = (graphemes.last(), self.line_content.get(end)) {
+            let joined = format!("{}{}", last.grapheme, next.grapheme);
+            if joined.graphemes(true).next() == Some(last.grapheme.as_str()) {
+                break;
+            }
+            text.push_str(&next.grapheme);
+            end = end.saturating_add(1);
+            graphemes = Self::segment(&text);
         }
 
-        Self {
-            line_content: content_graphemes,
+        self.line_content.splice(start..end, graphemes);
+        self.update_prefixes_from(start);
+    }
+
+    // Recompute the cached prefixes for every grapheme from `line_location` onwards
+    fn update_prefixes_from(&mut self, line_location: usize) {
+        self.prefix_widths.truncate(line_location.saturating_add(1));
+        self.prefix_chars.truncate(line_location.saturating_add(1));
+
+        let mut width = self.prefix_widths[line_location];
+        let mut chars = self.prefix_chars[line_location];
+        for grapheme in &self.line_content[line_location..] {
+            width = grapheme.rendered_width.saturating_add(width);
+            chars = chars.saturating_add(grapheme.grapheme.chars().count());
+            self.prefix_widths.push(width);
+            self.prefix_chars.push(chars);
         }
     }
 
@@ -26,76 +96,237 @@ impl Line {
     }
 
     pub fn get_total_width(&self) -> usize {
-        let mut total_length: usize = 0;
-        for grapheme in &self.line_content {
-            total_length = grapheme.rendered_width.saturating_add(total_length);
-        }
-
-        total_length
+        self.get_nth_location(self.len())
     }
 
-    pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
-        let mut result_string: String = String::new();
+    // The text shown between the given screen columns, split into runs shown the same way
+    pub fn get_visible_graphemes(&self, range: Range<usize>) -> Vec<Span> {
+        let mut result: Vec<Span> = Vec::new();
         if range.start >= range.end {
-            return result_string;
+            return result;
         }
 
-        let mut screen_position = 0;
         let range_start = range.start;
         let range_end: usize = range.end.min(self.get_total_width());
+        let trailing_start = self.trailing_whitespace_start();
 
-        for grapheme in &self.line_content {
+        // Skip straight to the first grapheme overlapping the visible range
+        let first_location = self.get_location_at_col(range_start);
+        let mut screen_position = self.get_nth_location(first_location);
+
+        for (location, grapheme) in self.line_content.iter().enumerate().skip(first_location) {
             let grapheme_end = grapheme.rendered_width.saturating_add(screen_position);
 
             if screen_position >= range_end {
                 break;
             }
 
-            if grapheme_end > range_start {
-                if (screen_position < range_start && grapheme_end >= range_start)
-                    || (screen_position < range_end && grapheme_end > range_end)
-                {
-                    result_string.push_str("...");
-                } else if let Some(replacement) = grapheme.replacement {
-                    result_string.push(replacement);
-                } else {
-                    result_string.push_str(&grapheme.grapheme);
+            let shown = if screen_position < range_start || grapheme_end > range_end {
+                // Only part of the grapheme is visible, mark its visible columns instead. The
+                // ellipsis is ambiguous-width, so it may take two of them, leaving one to a space.
+                let visible_width = grapheme_end
+                    .min(range_end)
+                    .saturating_sub(screen_position.max(range_start));
+                let ellipsis_width = grapheme_width("…").max(1);
+                let mut marks = "…".repeat(visible_width.checked_div(ellipsis_width).unwrap_or(0));
+                marks.push_str(&" ".repeat(visible_width.checked_rem(ellipsis_width).unwrap_or(0)));
+                marks
+            } else {
+                grapheme
+                    .replacement
+                    .clone()
+                    .unwrap_or_else(|| grapheme.grapheme.clone())
+            };
+            let trailing = location >= trailing_start;
+            match result.last_mut() {
+                Some(span) if span.kind == grapheme.kind && span.trailing == trailing => {
+                    span.text.push_str(&shown);
                 }
+                _ => result.push(Span {
+                    text: shown,
+                    kind: grapheme.kind,
+                    trailing,
+                }),
             }
             screen_position = grapheme_end;
         }
 
-        result_string
+        result
+    }
+
+    // Location of the first of the whitespace graphemes ending the line
+    fn trailing_whitespace_start(&self) -> usize {
+        self.line_content
+            .iter()
+            .rposition(|grapheme| !grapheme.kind.is_whitespace())
+            .map_or(0, |location| location.saturating_add(1))
+    }
+
+    pub fn grapheme(&self, line_location: usize) -> Option<&str> {
+        self.line_content
+            .get(line_location)
+            .map(|grapheme| grapheme.grapheme.as_str())
+    }
+
+    // Screen column at which the grapheme at `line_location` starts
+    pub fn get_nth_location(&self, line_location: usize) -> usize {
+        self.prefix_widths[line_location.min(self.len())]
     }
 
-    pub fn get_nth_location(&self, col: usize) -> usize {
-        let mut total_prev_width: usize = 0;
-        for t in 0..col.min(self.line_content.len()) {
-            total_prev_width = self.line_content[t]
-                .rendered_width
-                .saturating_add(total_prev_width);
+    // Index of the grapheme covering the given screen column, or the line length past its end
+    pub fn get_location_at_col(&self, col: usize) -> usize {
+        if col >= self.get_total_width() {
+            return self.len();
         }
+        self.prefix_widths
+            .partition_point(|width| *width <= col)
+            .saturating_sub(1)
+    }
+
+    // Number of chars taken up by the graphemes before `line_location`
+    pub fn char_offset(&self, line_location: usize) -> usize {
+        self.prefix_chars[line_location.min(self.len())]
+    }
 
-        total_prev_width
+    // Index of the first grapheme starting at or after the given char offset
+    pub fn location_at_char_offset(&self, char_offset: usize) -> usize {
+        self.prefix_chars
+            .partition_point(|chars| *chars < char_offset)
+            .min(self.len())
+    }
+
+    pub fn append(&mut self, mut other: Line) {
+        let appended_location = self.len();
+        self.line_content.append(&mut other.line_content);
+        self.update_prefixes_from(appended_location);
+
+        // The first appended grapheme may cluster with the last one already in the line
+        if let Some(last_location) = appended_location.checked_sub(1) {
+            let last = self.line_content[last_location].grapheme.clone();
+            self.resegment(last_location, appended_location, &last);
+        }
     }
 
-    pub fn append(&mut self, other: &mut Vec<TextGrapheme>) {
-        self.line_content.append(other);
+    // The grapheme in front of the given location, which an edit at that location may extend
+    fn previous_grapheme(&self, line_location: usize) -> (usize, String) {
+        match line_location.checked_sub(1) {
+            Some(previous) => (previous, self.line_content[previous].grapheme.clone()),
+            None => (line_location, String::new()),
+        }
     }
 
     /**
      * Command Operations
      */
     pub fn insert(&mut self, inserted_char: char, line_insert_location: usize) {
-        let new_grapheme = TextGrapheme::new(&format!("{inserted_char}"));
-        self.line_content.insert(line_insert_location, new_grapheme);
+        let (start, mut text) = self.previous_grapheme(line_insert_location);
+        text.push(inserted_char);
+        self.resegment(start, line_insert_location, &text);
     }
 
     pub fn remove(&mut self, line_remove_location: usize) {
-        self.line_content.remove(line_remove_location);
+        let (start, text) = self.previous_grapheme(line_remove_location);
+        self.resegment(start, line_remove_location.saturating_add(1), &text);
+    }
+
+    // Remove only the last code point of the grapheme at `line_location`, e.g. just the accent
+    pub fn remove_code_point(&mut self, line_location: usize) {
+        let mut text = self.line_content[line_location].grapheme.clone();
+        text.pop();
+        if text.is_empty() {
+            self.remove(line_location);
+            return;
+        }
+        self.resegment(line_location, line_location.saturating_add(1), &text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Line;
+    use crate::editor::terminal::{grapheme_width, WidthModel};
+    use unicode_segmentation::UnicodeSegmentation;
+
+    fn assert_segmented(line: &Line) {
+        let text = line.to_string();
+        let graphemes: Vec<&str> = line
+            .line_content
+            .iter()
+            .map(|grapheme| grapheme.grapheme.as_str())
+            .collect();
+        assert_eq!(graphemes, text.graphemes(true).collect::<Vec<_>>());
+        assert_eq!(line.char_offset(line.len()), text.chars().count());
+    }
+
+    fn type_text(line: &mut Line, text: &str) {
+        for typed in text.chars() {
+            let location = line.location_at_char_offset(line.char_offset(line.len()));
+            line.insert(typed, location);
+            assert_segmented(line);
+        }
+    }
+
+    #[test]
+    fn typed_combining_sequences_form_single_graphemes() {
+        let mut line = Line::from("");
+        type_text(&mut line, "e\u{301}👩\u{200d}👩\u{200d}👧🇩🇪🇫🇷");
+        assert_eq!(line.len(), 4);
+        assert_eq!(line.get_total_width(), 1 + 2 + 2 + 2);
+    }
+
+    #[test]
+    fn edits_regroup_the_neighboring_graphemes() {
+        // A base typed in front of a lone combining mark takes it over
+        let mut line = Line::from("\u{301}x");
+        line.insert('e', 0);
+        assert_segmented(&line);
+        assert_eq!(line.len(), 2);
+
+        // Removing the flag in front shifts the pairing of the regional indicators after it
+        let mut line = Line::from("🇩🇪🇫🇷🇮");
+        line.remove(0);
+        assert_segmented(&line);
+        line.insert('🇩', 0);
+        assert_segmented(&line);
+        assert_eq!(line.len(), 2);
+
+        // A control char between a base and a combining mark keeps them apart until removed
+        let mut line = Line::from("a\u{200b}\u{301}");
+        assert_eq!(line.len(), 3);
+        line.remove(1);
+        assert_segmented(&line);
+        assert_eq!(line.len(), 1);
+
+        let mut line = Line::from("a\u{301}b");
+        line.remove_code_point(0);
+        assert_segmented(&line);
+        assert_eq!(line.to_string(), "ab");
     }
 
-    pub fn delete(&mut self, line_delete_location: usize) {
-        self.line_content.remove(line_delete_location);
+    #[test]
+    fn appended_lines_are_joined_into_clusters() {
+        let mut line = Line::from("e");
+        line.append(Line::from("\u{301}x"));
+        assert_segmented(&line);
+        assert_eq!(line.len(), 2);
+    }
+
+    #[test]
+    fn cut_off_graphemes_fill_just_their_visible_columns() {
+        WidthModel::set_current(WidthModel {
+            ambiguous_width: 2,
+            ..WidthModel::default()
+        });
+        let line = Line::from("界界x");
+        for range in [1..4, 0..3, 1..5] {
+            let width: usize = line
+                .get_visible_graphemes(range.clone())
+                .iter()
+                .flat_map(|span| span.text.graphemes(true))
+                .map(grapheme_width)
+                .sum();
+            assert_eq!(width, range.len());
+        }
+        WidthModel::set_current(WidthModel::default());
     }
 }
@@ -1,49 +1,86 @@
-use unicode_width::UnicodeWidthStr;
+use crate::editor::terminal::grapheme_width;
 
-#[derive(Debug)]
-pub enum GraphemeWidth {
-    Half,
-    Full,
+// What kind of character a grapheme is, which decides how it is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphemeKind {
+    Text,
+    Space,
+    Tab,
+    NonBreakingSpace,
+    // C0 and C1 controls, shown in caret (`^G`) or hex (`<85>`) notation
+    Control,
+    // Characters taking up no room on their own (e.g. ZWSP, BOM, a lone combining mark),
+    // shown by code point
+    ZeroWidth,
+    // Marks and overrides changing the direction of the text around them, shown by code point
+    Bidi,
 }
 
-impl GraphemeWidth {
-    pub fn saturating_add(&self, other: usize) -> usize {
-        match self {
-            GraphemeWidth::Full => other.saturating_add(2),
-            GraphemeWidth::Half => other.saturating_add(1),
-        }
+impl GraphemeKind {
+    pub fn is_whitespace(self) -> bool {
+        matches!(self, Self::Space | Self::Tab | Self::NonBreakingSpace)
     }
 }
 
 #[derive(Debug)]
 pub struct TextGrapheme {
     pub grapheme: String,
-    pub rendered_width: GraphemeWidth,
-    pub replacement: Option<char>,
+    pub rendered_width: usize,
+    pub replacement: Option<String>,
+    pub kind: GraphemeKind,
 }
 
 impl TextGrapheme {
     pub fn new(grapheme: &str) -> Self {
-        let mut grapheme_string = grapheme;
-        let grapheme_width = grapheme_string.width();
-
-        if grapheme_string == "\t" {
-            grapheme_string = " ";
-        }
-
-        let mut replacement = None;
-        if grapheme_width == 0 {
-            replacement = Some('·');
-        }
+        // Tabs are kept in the content (so they are saved as-is), but rendered as a single space
+        let (kind, replacement) = if grapheme == " " {
+            (GraphemeKind::Space, None)
+        } else if grapheme == "\t" {
+            (GraphemeKind::Tab, Some(" ".to_string()))
+        } else if matches!(grapheme, "\u{a0}" | "\u{202f}") {
+            (GraphemeKind::NonBreakingSpace, None)
+        } else if let Some(notation) = control_notation(grapheme) {
+            (GraphemeKind::Control, Some(notation))
+        } else if grapheme.chars().any(is_bidi_control) {
+            (GraphemeKind::Bidi, Some(code_point_notation(grapheme)))
+        } else if grapheme_width(grapheme) == 0 {
+            (GraphemeKind::ZeroWidth, Some(code_point_notation(grapheme)))
+        } else {
+            (GraphemeKind::Text, None)
+        };
 
         Self {
-            grapheme: grapheme_string.to_string(),
-            rendered_width: if grapheme_width >= 2 {
-                GraphemeWidth::Full
-            } else {
-                GraphemeWidth::Half
-            },
+            grapheme: grapheme.to_string(),
+            rendered_width: replacement
+                .as_deref()
+                .map_or_else(|| grapheme_width(grapheme), str::len),
             replacement,
+            kind,
         }
     }
 }
+
+fn control_notation(grapheme: &str) -> Option<String> {
+    let control = grapheme.chars().next().filter(|c| c.is_control())?;
+    let code = u32::from(control);
+    match code {
+        // ^@ to ^_ for C0, and ^? for DEL
+        0x00..=0x1f | 0x7f => char::from_u32(code ^ 0x40).map(|c| format!("^{c}")),
+        _ => Some(format!("<{code:02X}>")),
+    }
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+fn code_point_notation(grapheme: &str) -> String {
+    let code_points: Vec<String> = grapheme
+        .chars()
+        .map(|c| format!("<{:04X}>", u32::from(c)))
+        .collect();
+    code_points.concat()
+}
@@ -0,0 +1,80 @@
+// Brackets and quotes typed in pairs, with the closer inserted along with the opener
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+// What typing a char does, given the chars on either side of the caret
+#[derive(Debug, PartialEq, Eq)]
+pub enum Typed {
+    Insert,
+    // Insert the char and this closer after it, leaving the caret in between
+    InsertPair(char),
+    // Move over the closer that is already there instead of adding another one
+    TypeOver,
+}
+
+// There is no syntax highlighting to tell strings and comments from code, so only the line
+// decides: a pair is only started where it can't be meant to go around text that follows, and
+// a quote right after a letter or digit is taken for an apostrophe. The closer after the caret
+// is only typed over if the line has all the closers it needs without another one, as it then
+// most likely came with its opener.
+pub fn typed(char: char, line: &str, before: Option<char>, after: Option<char>) -> Typed {
+    let pair = PAIRS.iter().find(|(_, closer)| *closer == char);
+    if after == Some(char) && pair.is_some_and(|pair| is_balanced(line, *pair)) {
+        return Typed::TypeOver;
+    }
+    let Some((opener, closer)) = PAIRS.iter().find(|(opener, _)| *opener == char) else {
+        return Typed::Insert;
+    };
+    let is_quote = opener == closer;
+    if is_quote && before.is_some_and(char::is_alphanumeric) {
+        return Typed::Insert;
+    }
+    let free_after = after
+        .is_none_or(|after| after.is_whitespace() || matches!(after, ')' | ']' | '}' | ',' | ';'));
+    if free_after {
+        Typed::InsertPair(*closer)
+    } else {
+        Typed::Insert
+    }
+}
+
+fn is_balanced(line: &str, (opener, closer): (char, char)) -> bool {
+    if opener == closer {
+        return line.matches(opener).count().is_multiple_of(2);
+    }
+    line.matches(opener).count() <= line.matches(closer).count()
+}
+
+// Whether the caret sits inside an empty pair, which Backspace then removes as a whole
+pub fn is_empty_pair(before: Option<char>, after: Option<char>) -> bool {
+    PAIRS
+        .iter()
+        .any(|(opener, closer)| before == Some(*opener) && after == Some(*closer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_empty_pair, typed, Typed};
+
+    #[test]
+    fn pairs_depend_on_the_chars_around_the_caret() {
+        assert_eq!(typed('(', "", None, None), Typed::InsertPair(')'));
+        assert_eq!(
+            typed('{', "f( )", Some(' '), Some(')')),
+            Typed::InsertPair('}')
+        );
+        // Going around text that follows is left to the closer typed later
+        assert_eq!(typed('(', "x", None, Some('x')), Typed::Insert);
+        assert_eq!(typed(')', "f()", Some('('), Some(')')), Typed::TypeOver);
+        assert_eq!(typed('"', "\"x\"", Some('x'), Some('"')), Typed::TypeOver);
+        assert_eq!(typed('\'', "n", Some('n'), None), Typed::Insert);
+        assert_eq!(typed('"', "=", Some('='), None), Typed::InsertPair('"'));
+        assert_eq!(typed('x', "", None, None), Typed::Insert);
+        // A closer the line still needs is typed, even in front of another one
+        assert_eq!(typed(')', "f(g(x)", Some('x'), Some(')')), Typed::Insert);
+        assert_eq!(typed('"', "\"a\"b\"", Some('a'), Some('"')), Typed::Insert);
+
+        assert!(is_empty_pair(Some('['), Some(']')));
+        assert!(!is_empty_pair(Some('['), Some(')')));
+        assert!(!is_empty_pair(None, Some(')')));
+    }
+}
@@ -0,0 +1,45 @@
+// Glyphs drawn in place of whitespace while it is being shown. Only what is drawn changes,
+// the buffer keeps the actual characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhitespaceGlyphs {
+    pub space: char,
+    pub tab: char,
+    pub nbsp: char,
+    pub eol: char,
+}
+
+impl Default for WhitespaceGlyphs {
+    fn default() -> Self {
+        Self {
+            space: '·',
+            tab: '→',
+            nbsp: '⍽',
+            eol: '¬',
+        }
+    }
+}
+
+impl WhitespaceGlyphs {
+    // Override some of the glyphs from a list like `space=.,eol=$`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut glyphs = Self::default();
+        for entry in spec.split(',') {
+            let Some((name, glyph)) = entry.split_once('=') else {
+                return Err(format!("Expected name=glyph, not {entry}"));
+            };
+            let mut chars = glyph.chars();
+            let (Some(glyph), None) = (chars.next(), chars.next()) else {
+                return Err(format!("The glyph for {name} must be a single character"));
+            };
+
+            match name {
+                "space" => glyphs.space = glyph,
+                "tab" => glyphs.tab = glyph,
+                "nbsp" => glyphs.nbsp = glyph,
+                "eol" => glyphs.eol = glyph,
+                _ => return Err(format!("Unknown whitespace {name}")),
+            }
+        }
+        Ok(glyphs)
+    }
+}